@@ -0,0 +1,70 @@
+use crate::types::*;
+
+fn collect_leaves(tree: &SerdeTree, leaves: &mut Vec<Filepath>) {
+    if let Some(node) = &tree.node {
+        for child in node.children.iter() {
+            collect_leaves(child, leaves);
+        }
+    }
+    if let Some(leaf) = &tree.leaf {
+        leaves.push(leaf.filepath.clone());
+    }
+}
+
+/// Splits the photos of `tree` into the suggested clusters and the photos outside them.
+fn partition(tree: &SerdeTree, clusters: &mut Vec<Cluster>, rest: &mut Vec<Filepath>) {
+    match &tree.node {
+        Some(node) if node.details.selected => {
+            let mut cluster = Vec::new();
+            collect_leaves(tree, &mut cluster);
+            clusters.push(Cluster(cluster));
+        }
+        Some(node) => {
+            for child in node.children.iter() {
+                partition(child, clusters, rest);
+            }
+        }
+        None => collect_leaves(tree, rest),
+    }
+}
+
+/// Groups of similar photos, earliest first as they are in the tree.
+fn collect_groups(tree: &SerdeTree, groups: &mut Vec<Cluster>) {
+    if let Some(node) = &tree.node {
+        if node.details.group.is_some() {
+            let mut group = Vec::new();
+            collect_leaves(tree, &mut group);
+            groups.push(Cluster(group));
+        } else {
+            for child in node.children.iter() {
+                collect_groups(child, groups);
+            }
+        }
+    }
+}
+
+/// The `Commit` the suggested clusters of `tree` amount to, so that `reorganize` can run
+/// without nodes picked by hand.
+pub fn auto_commit(tree: &SerdeTree, request: AutoReorganize) -> Commit {
+    let mut clusters = Vec::new();
+    let mut rest = Vec::new();
+    partition(tree, &mut clusters, &mut rest);
+
+    let mut collapsed_groups = Vec::new();
+    if request.collapse_groups {
+        collect_groups(tree, &mut collapsed_groups);
+    }
+
+    Commit {
+        clusters: Clusters(clusters),
+        folder: request.folder,
+        dryrun: request.dryrun,
+        move_duplicates: request.move_duplicates,
+        collapsed_groups,
+        misc: if request.move_unclustered {
+            rest
+        } else {
+            Vec::new()
+        },
+    }
+}
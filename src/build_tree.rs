@@ -1,18 +1,229 @@
 use std::{
     cell::RefCell,
-    collections::{HashMap, LinkedList},
-    path::PathBuf,
+    collections::{HashMap, HashSet, LinkedList},
     rc::Rc,
 };
 
-use geoutils::Location;
-use ndarray::{ArrayBase, Dim, OwnedRepr};
+use ndarray::Array1;
 
 use crate::types::*;
 
+/// `(parent, child, lambda, size)` rows of an HDBSCAN condensed tree. Points are numbered
+/// from 0, clusters from the number of points with the root first.
+pub type CondensedTree = Array1<(usize, usize, f64, usize)>;
+
+fn to_leaf(photo: &Photo) -> Tree {
+    Tree::Leaf(photo.filepath.clone(), photo.coord, photo.details.clone())
+}
+
+/// A single photo becomes a leaf, a group of similar photos a synthetic node.
+pub fn group_to_tree(group: &PhotoGroup) -> Tree {
+    match group.photos.as_slice() {
+        [photo] => to_leaf(photo),
+        photos => {
+            let leaves = photos
+                .iter()
+                .map(|photo| Rc::new(RefCell::new(to_leaf(photo))))
+                .collect();
+            let details = NodeDetails {
+                group: group.kind,
+                ..NodeDetails::default()
+            };
+            Tree::Node(NumberOfLeaves(photos.len()), leaves, details)
+        }
+    }
+}
+
+fn set_outlier_score(tree: &mut Tree, score: f64) {
+    match tree {
+        Tree::Node(_, children, _) => {
+            for child in children.iter() {
+                set_outlier_score(&mut child.borrow_mut(), score);
+            }
+        }
+        Tree::Leaf(_, _, details) => details.outlier_score = Some(score),
+    }
+}
+
+/// Holds the points that fell out of the root cluster, so that they are not mistaken for
+/// clusters of one.
+pub fn unclustered_node(children: LinkedList<Rc<RefCell<Tree>>>) -> Rc<RefCell<Tree>> {
+    let details = NodeDetails {
+        unclustered: true,
+        ..NodeDetails::default()
+    };
+    Rc::new(RefCell::new(Tree::Node(
+        NumberOfLeaves(0),
+        children,
+        details,
+    )))
+}
+
+/// Adds `groups` to the unclustered points under `root`.
+pub fn add_unclustered(root: &mut Tree, groups: &[PhotoGroup]) {
+    let Tree::Node(n, children, _) = root else {
+        return;
+    };
+    if groups.is_empty() {
+        return;
+    }
+
+    let leaves = groups
+        .iter()
+        .map(|group| Rc::new(RefCell::new(group_to_tree(group))));
+    let unclustered = children
+        .iter()
+        .find(|child| matches!(&*child.borrow(), Tree::Node(_, _, details) if details.unclustered));
+    match unclustered {
+        Some(node) => {
+            if let Tree::Node(_, members, _) = &mut *node.borrow_mut() {
+                members.extend(leaves);
+            }
+        }
+        None => children.push_back(unclustered_node(leaves.collect())),
+    }
+
+    *n = children
+        .iter()
+        .fold(NumberOfLeaves(0), |accum, child| accum + recount(child));
+}
+
+/// The largest finite lambda of `condensed_tree`. Points merged at zero distance get an
+/// infinite lambda, which is read as this one instead.
+fn max_finite_lambda(condensed_tree: &CondensedTree) -> f64 {
+    condensed_tree
+        .iter()
+        .map(|(_, _, lambda, _)| *lambda)
+        .filter(|lambda| lambda.is_finite())
+        .fold(0., f64::max)
+}
+
+/// GLOSH outlier score of every point: 0 for a point as dense as the densest one of the
+/// cluster it falls out of, approaching 1 the sparser it is in comparison.
+fn outlier_scores(number_of_points: usize, condensed_tree: &CondensedTree) -> Vec<f64> {
+    let parent_of: HashMap<usize, usize> = condensed_tree
+        .iter()
+        .filter(|(_, child, _, _)| *child >= number_of_points)
+        .map(|(parent, child, _, _)| (*child, *parent))
+        .collect();
+    // Otherwise duplicates would make every other point of their clusters an outlier.
+    let finite_lambda = max_finite_lambda(condensed_tree);
+
+    // The largest lambda of the points below each cluster.
+    let mut max_lambda: HashMap<usize, f64> = HashMap::new();
+    for (parent, child, lambda, _) in condensed_tree.iter() {
+        if *child >= number_of_points {
+            continue;
+        }
+        let lambda = lambda.min(finite_lambda);
+        let mut cluster = Some(*parent);
+        while let Some(c) = cluster {
+            let max = max_lambda.entry(c).or_insert(0.);
+            if *max >= lambda {
+                break;
+            }
+            *max = lambda;
+            cluster = parent_of.get(&c).copied();
+        }
+    }
+
+    let mut scores = vec![0.; number_of_points];
+    for (parent, child, lambda, _) in condensed_tree.iter() {
+        if *child >= number_of_points {
+            continue;
+        }
+        let max = max_lambda[parent];
+        let lambda = lambda.min(finite_lambda);
+        scores[*child] = if lambda >= max { 0. } else { 1. - lambda / max };
+    }
+    scores
+}
+
+/// Clusters of the condensed tree making up the flat partition `selection` suggests. The
+/// root is never selected, so a tree with no split suggests no cluster.
+fn select_clusters(
+    number_of_points: usize,
+    condensed_tree: &CondensedTree,
+    selection: ClusterSelection,
+) -> HashSet<usize> {
+    let root = number_of_points;
+    // Infinite lambdas would swamp the stability of every other cluster.
+    let max_lambda = max_finite_lambda(condensed_tree);
+
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut birth: HashMap<usize, f64> = HashMap::from([(root, 0.)]);
+    for (parent, child, lambda, _) in condensed_tree.iter() {
+        if *child >= number_of_points {
+            children.entry(*parent).or_default().push(*child);
+            birth.insert(*child, lambda.min(max_lambda));
+        }
+    }
+
+    // The mass each cluster holds over its lifetime: every point or child cluster counts
+    // from the cluster's birth until it leaves.
+    let mut stability: HashMap<usize, f64> = HashMap::new();
+    for (parent, _, lambda, size) in condensed_tree.iter() {
+        let lifetime = lambda.min(max_lambda) - birth.get(parent).copied().unwrap_or(0.);
+        *stability.entry(*parent).or_default() += lifetime * *size as f64;
+    }
+
+    // Clusters worth keeping over their descendants, deciding bottom-up.
+    fn keep(
+        cluster: usize,
+        children: &HashMap<usize, Vec<usize>>,
+        stability: &HashMap<usize, f64>,
+        selection: ClusterSelection,
+        kept: &mut HashSet<usize>,
+    ) -> f64 {
+        let own = stability.get(&cluster).copied().unwrap_or(0.);
+        let Some(subclusters) = children.get(&cluster) else {
+            kept.insert(cluster);
+            return own;
+        };
+        let descendants: f64 = subclusters
+            .iter()
+            .map(|child| keep(*child, children, stability, selection, kept))
+            .sum();
+        if selection == ClusterSelection::ExcessOfMass && own >= descendants {
+            kept.insert(cluster);
+            own
+        } else {
+            descendants
+        }
+    }
+    let mut kept = HashSet::new();
+    keep(root, &children, &stability, selection, &mut kept);
+
+    // The topmost kept clusters below the root.
+    let mut selected = HashSet::new();
+    let mut pending = children.get(&root).cloned().unwrap_or_default();
+    while let Some(cluster) = pending.pop() {
+        if kept.contains(&cluster) {
+            selected.insert(cluster);
+        } else if let Some(subclusters) = children.get(&cluster) {
+            pending.extend(subclusters);
+        }
+    }
+    selected
+}
+
+/// Node sizes in the condensed tree count groups rather than photos.
+pub fn recount(tree: &Rc<RefCell<Tree>>) -> NumberOfLeaves {
+    match &mut *tree.borrow_mut() {
+        Tree::Node(n, children, _) => {
+            *n = children
+                .iter()
+                .fold(NumberOfLeaves(0), |accum, child| accum + recount(child));
+            *n
+        }
+        Tree::Leaf(_, _, _) => NumberOfLeaves(1),
+    }
+}
+
 pub fn build_tree(
-    l: &Vec<(PathBuf, Location)>,
-    condensed_tree: &ArrayBase<OwnedRepr<(usize, usize, f64, usize)>, Dim<[usize; 1]>>,
+    l: &[PhotoGroup],
+    selection: ClusterSelection,
+    condensed_tree: &CondensedTree,
 ) -> Tree {
     // child -> (parent, num of descendands)
     let cluster_map = condensed_tree.fold(
@@ -25,21 +236,18 @@ pub fn build_tree(
 
     let mut mns: HashMap<usize, _> = HashMap::new();
 
+    let scores = outlier_scores(l.len(), condensed_tree);
     let get_leaf = |child: usize| {
-        let (path, location) = l
+        let group = l
             .get(child)
             .expect("child index not is not a singleton cluster");
-        let filepath = Filepath(String::from(
-            path.to_str().expect("Cannot convert path to string"),
-        ));
-        let coord = Coord {
-            lat: location.latitude(),
-            lon: location.longitude(),
-        };
-        Tree::Leaf(filepath, coord)
+        let mut tree = group_to_tree(group);
+        set_outlier_score(&mut tree, scores[child]);
+        tree
     };
 
     let l_size = l.len();
+    let selected = select_clusters(l_size, condensed_tree, selection);
     for (child, (parent, num_of_leaves)) in cluster_map.iter() {
         let node_to_update_parent_with = match mns.get(child) {
             None => {
@@ -49,7 +257,14 @@ pub fn build_tree(
                     mns.insert(*child, leaf.clone());
                     leaf
                 } else {
-                    let node = Rc::new(RefCell::new(Tree::Node(*num_of_leaves, LinkedList::new())));
+                    let node = Rc::new(RefCell::new(Tree::Node(
+                        *num_of_leaves,
+                        LinkedList::new(),
+                        NodeDetails {
+                            selected: selected.contains(child),
+                            ..NodeDetails::default()
+                        },
+                    )));
 
                     mns.insert(*child, node.clone());
                     node
@@ -62,8 +277,8 @@ pub fn build_tree(
             Some(parent) => {
                 let mut parent = parent.borrow_mut();
                 match &mut *parent {
-                    Tree::Node(_, l) => l.push_back(node_to_update_parent_with),
-                    Tree::Leaf(_, _) => panic!("leaf cannot be a parent"),
+                    Tree::Node(_, l, _) => l.push_back(node_to_update_parent_with),
+                    Tree::Leaf(_, _, _) => panic!("leaf cannot be a parent"),
                 }
             }
             None => {
@@ -72,6 +287,10 @@ pub fn build_tree(
                         let node = Rc::new(RefCell::new(Tree::Node(
                             *num_of_leaves,
                             LinkedList::from([node_to_update_parent_with]),
+                            NodeDetails {
+                                selected: selected.contains(parent),
+                                ..NodeDetails::default()
+                            },
                         )));
 
                         mns.insert(*parent, node);
@@ -82,21 +301,78 @@ pub fn build_tree(
         }
     }
 
-    let children_of_root: LinkedList<_> = condensed_tree
+    let (noise, clusters): (Vec<_>, Vec<_>) = condensed_tree
         .iter()
         .filter(|(parent, _, _, _)| !cluster_map.contains_key(parent))
-        .map(|(_, child, _, _)| mns.get(child).expect("Cannot find child").clone())
-        .collect();
+        .map(|(_, child, _, _)| (*child, mns.get(child).expect("Cannot find child").clone()))
+        .partition(|(child, _)| *child < l_size);
 
-    let total_number_of_leaves =
-        children_of_root
-            .iter()
-            .fold(NumberOfLeaves(0), |accum, child| -> NumberOfLeaves {
-                match *child.borrow() {
-                    Tree::Node(n, _) => accum + n,
-                    Tree::Leaf(_, _) => accum + NumberOfLeaves(1),
-                }
-            });
+    let mut children_of_root: LinkedList<_> = clusters.into_iter().map(|(_, tree)| tree).collect();
+    if !noise.is_empty() {
+        children_of_root.push_back(unclustered_node(
+            noise.into_iter().map(|(_, tree)| tree).collect(),
+        ));
+    }
+
+    let total_number_of_leaves = children_of_root
+        .iter()
+        .fold(NumberOfLeaves(0), |accum, child| -> NumberOfLeaves {
+            accum + recount(child)
+        });
 
-    Tree::Node(total_number_of_leaves, children_of_root)
+    Tree::Node(
+        total_number_of_leaves,
+        children_of_root,
+        NodeDetails::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INF: f64 = f64::INFINITY;
+
+    #[test]
+    fn duplicates_do_not_make_their_neighbours_outliers() {
+        // Points 0 and 1 are duplicates, with point 2 nearby; points 4 and 5 are the
+        // densest pair otherwise.
+        let condensed_tree = CondensedTree::from(vec![
+            (6, 7, 0.5, 3),
+            (6, 8, 0.5, 3),
+            (7, 0, INF, 1),
+            (7, 1, INF, 1),
+            (7, 2, 1.0, 1),
+            (8, 3, 2.0, 1),
+            (8, 4, 4.0, 1),
+            (8, 5, 4.0, 1),
+        ]);
+
+        let scores = outlier_scores(6, &condensed_tree);
+        assert_eq!(scores, vec![0., 0., 0.75, 0.5, 0., 0.]);
+    }
+
+    #[test]
+    fn duplicates_do_not_outweigh_a_stable_parent() {
+        // Cluster 7 lives long before splitting into the duplicates 0 and 1 and the pair
+        // 2 and 3.
+        let condensed_tree = CondensedTree::from(vec![
+            (6, 7, 0.01, 4),
+            (6, 8, 0.01, 2),
+            (7, 9, 0.5, 2),
+            (7, 10, 0.5, 2),
+            (9, 0, INF, 1),
+            (9, 1, INF, 1),
+            (10, 2, 0.55, 1),
+            (10, 3, 0.55, 1),
+            (8, 4, 0.6, 1),
+            (8, 5, 0.6, 1),
+        ]);
+
+        let selected = select_clusters(6, &condensed_tree, ClusterSelection::ExcessOfMass);
+        assert_eq!(selected, HashSet::from([7, 8]));
+
+        let selected = select_clusters(6, &condensed_tree, ClusterSelection::Leaf);
+        assert_eq!(selected, HashSet::from([8, 9, 10]));
+    }
 }
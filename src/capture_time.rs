@@ -0,0 +1,251 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::time_zone::{local_utc_offset_at, utc_offset_at};
+use crate::types::*;
+
+/// Offsets in use range from -12:00 to +14:00.
+const MAX_OFFSET_SECONDS: i64 = 14 * 3600;
+
+impl MyDateTime {
+    /// `None` for a date the calendar does not have, such as the `0000:00:00` some cameras
+    /// write when their clock was never set.
+    pub fn to_naive(self) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)?.and_hms_opt(
+            self.hour as u32,
+            self.minute as u32,
+            self.second as u32,
+        )
+    }
+
+    pub fn from_naive(naive: NaiveDateTime) -> Option<MyDateTime> {
+        Some(MyDateTime {
+            year: naive.year().try_into().ok()?,
+            month: naive.month() as u8,
+            day: naive.day() as u8,
+            hour: naive.hour() as u8,
+            minute: naive.minute() as u8,
+            second: naive.second() as u8,
+        })
+    }
+
+    /// Converts seconds since 1970-01-01 00:00:00 UTC to a UTC date and time.
+    pub fn from_unix_timestamp(seconds: i64) -> Option<MyDateTime> {
+        MyDateTime::from_naive(DateTime::from_timestamp(seconds, 0)?.naive_utc())
+    }
+
+    /// Seconds since 1970-01-01 00:00:00, treating `self` as UTC.
+    pub fn to_unix_timestamp(self) -> Option<i64> {
+        Some(self.to_naive()?.and_utc().timestamp())
+    }
+
+    /// Parses the date and time of an ISO 8601 value (`YYYY-MM-DDThh:mm:ss` with optional
+    /// fraction and zone). The zone, if any, is ignored.
+    pub fn from_iso8601(value: &str) -> Option<MyDateTime> {
+        let (date, time) = value.trim().split_once('T').unwrap_or((value.trim(), ""));
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time
+            .split(['+', '-', 'Z'])
+            .next()
+            .unwrap_or("")
+            .split(':')
+            .collect();
+
+        let time_part = |i: usize| -> Option<u8> {
+            match time.get(i) {
+                Some(t) if !t.is_empty() => t.split('.').next()?.parse().ok(),
+                _ => Some(0),
+            }
+        };
+
+        Some(MyDateTime {
+            year: date.first()?.parse().ok()?,
+            month: date.get(1)?.parse().ok()?,
+            day: date.get(2)?.parse().ok()?,
+            hour: time_part(0)?,
+            minute: time_part(1)?,
+            second: time_part(2)?,
+        })
+    }
+}
+
+/// Returns the zone of an ISO 8601 value as seconds east of UTC: `Z` is 0,
+/// `+02:00` is 7200. `None` when the value carries no zone.
+pub fn iso8601_offset_seconds(value: &str) -> Option<i64> {
+    let (_, time) = value.trim().split_once('T')?;
+    if time.ends_with('Z') {
+        return Some(0);
+    }
+
+    let sign_position = time.rfind(['+', '-'])?;
+    parse_utc_offset(&time[sign_position..])
+}
+
+/// Parses a UTC offset such as `+02:00`, `-0530` or `Z` into seconds east of UTC.
+pub fn parse_utc_offset(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value == "Z" {
+        return Some(0);
+    }
+
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let zone = value[1..].replace(':', "");
+    if zone.len() != 4 {
+        return None;
+    }
+    let hours: i64 = zone[..2].parse().ok()?;
+    let minutes: i64 = zone[2..].parse().ok()?;
+
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+impl ZonedDateTime {
+    pub fn from_unix_timestamp(seconds: i64, offset_seconds: i64) -> Option<ZonedDateTime> {
+        Some(ZonedDateTime {
+            local: MyDateTime::from_unix_timestamp(seconds + offset_seconds)?,
+            offset_seconds,
+        })
+    }
+
+    pub fn to_unix_timestamp(self) -> Option<i64> {
+        Some(self.local.to_unix_timestamp()? - self.offset_seconds)
+    }
+}
+
+impl Ord for ZonedDateTime {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.to_unix_timestamp(), self.offset_seconds)
+            .cmp(&(other.to_unix_timestamp(), other.offset_seconds))
+    }
+}
+
+impl PartialOrd for ZonedDateTime {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PhotoMetadata {
+    /// Zone of the camera clock: recorded in the file, else the difference between the
+    /// camera and GPS clocks rounded to 15 minutes.
+    fn recorded_offset_seconds(&self) -> Option<i64> {
+        if self.utc_offset_seconds.is_some() {
+            return self.utc_offset_seconds;
+        }
+
+        let local = self.date_time?.to_unix_timestamp()?;
+        let utc = self.utc_date_time?.to_unix_timestamp()?;
+        let offset = ((local - utc) as f64 / 900.).round() as i64 * 900;
+        (offset.abs() <= MAX_OFFSET_SECONDS).then_some(offset)
+    }
+
+    /// Capture time in seconds since the Unix epoch, when the file pins it down.
+    pub fn unix_timestamp(&self) -> Option<i64> {
+        match (self.date_time, self.recorded_offset_seconds()) {
+            (Some(local), Some(offset)) => Some(local.to_unix_timestamp()? - offset),
+            _ => self.utc_date_time?.to_unix_timestamp(),
+        }
+    }
+
+    /// Capture time in the zone it was taken in. When the file does not record the zone,
+    /// it is looked up at `coord`; without a position UTC is assumed.
+    pub fn capture_time(&self, coord: Option<&Coord>) -> Option<ZonedDateTime> {
+        let local = self.date_time.filter(|local| local.to_naive().is_some());
+        if let (Some(local), Some(offset_seconds)) = (local, self.recorded_offset_seconds()) {
+            return Some(ZonedDateTime {
+                local,
+                offset_seconds,
+            });
+        }
+
+        match self.utc_date_time.and_then(MyDateTime::to_unix_timestamp) {
+            Some(seconds) => {
+                let offset_seconds = coord.map_or(0, |coord| utc_offset_at(coord, seconds));
+                ZonedDateTime::from_unix_timestamp(seconds, offset_seconds)
+            }
+            None => local.map(|local| ZonedDateTime {
+                local,
+                offset_seconds: coord.map_or(0, |coord| local_utc_offset_at(coord, local)),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> MyDateTime {
+        MyDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn converts_unix_timestamps() {
+        let local = date_time(2023, 7, 14, 22, 30);
+        assert_eq!(local.to_unix_timestamp(), Some(1_689_373_800));
+        assert_eq!(MyDateTime::from_unix_timestamp(1_689_373_800), Some(local));
+        assert_eq!(date_time(0, 0, 0, 0, 0).to_unix_timestamp(), None);
+    }
+
+    #[test]
+    fn parses_iso8601() {
+        let value = "2023-07-14T18:30:05.250+02:00";
+        let parsed = MyDateTime::from_iso8601(value).unwrap();
+        assert_eq!(
+            parsed,
+            MyDateTime {
+                second: 5,
+                ..date_time(2023, 7, 14, 18, 30)
+            }
+        );
+        assert_eq!(iso8601_offset_seconds(value), Some(7200));
+
+        assert_eq!(iso8601_offset_seconds("2023-07-14T16:30:05Z"), Some(0));
+        assert_eq!(iso8601_offset_seconds("2023-07-14T18:30:05"), None);
+        assert_eq!(
+            MyDateTime::from_iso8601("2023-07-14"),
+            Some(date_time(2023, 7, 14, 0, 0))
+        );
+    }
+
+    #[test]
+    fn parses_utc_offsets() {
+        assert_eq!(parse_utc_offset("+02:00"), Some(7200));
+        assert_eq!(parse_utc_offset("-0530"), Some(-19800));
+        assert_eq!(parse_utc_offset("Z"), Some(0));
+        assert_eq!(parse_utc_offset("CET"), None);
+    }
+
+    #[test]
+    fn takes_the_zone_from_the_camera_and_gps_clocks() {
+        let metadata = PhotoMetadata {
+            date_time: Some(date_time(2023, 7, 14, 20, 30)),
+            utc_date_time: Some(date_time(2023, 7, 14, 18, 29)),
+            ..PhotoMetadata::default()
+        };
+        let zoned = metadata.capture_time(None).unwrap();
+        assert_eq!(zoned.offset_seconds, 7200);
+        assert_eq!(zoned.local, date_time(2023, 7, 14, 20, 30));
+        assert_eq!(metadata.unix_timestamp(), Some(1_689_359_400));
+    }
+
+    #[test]
+    fn ignores_dates_the_calendar_does_not_have() {
+        let metadata = PhotoMetadata {
+            date_time: Some(date_time(2023, 2, 30, 12, 0)),
+            ..PhotoMetadata::default()
+        };
+        assert!(metadata.capture_time(None).is_none());
+        assert!(metadata.unix_timestamp().is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use std::{cell::RefCell, collections::LinkedList, rc::Rc};
+
+use ndarray::Array2;
+use petal_clustering::{Dbscan, Fit, HDbscan, Optics};
+use petal_neighbors::distance::Metric;
+
+use crate::build_tree::*;
+use crate::types::*;
+
+/// Builds the `Tree` of photo groups from `input`, one row of `[lat, lon]` per group, or
+/// `[lat, lon, hours]` for `SpaceTime`.
+pub trait ClusteringStrategy {
+    fn cluster(&self, groups: &[PhotoGroup], input: &Array2<f64>) -> Tree;
+}
+
+struct HdbscanStrategy<M> {
+    parameters: ClusteringParameters,
+    metric: M,
+    /// Boruvka prunes its search by the triangle inequality, so it is only used for
+    /// `Haversine`.
+    boruvka: bool,
+}
+
+impl<M: Metric<f64> + Clone + Sync> HdbscanStrategy<M> {
+    fn compute_condensed(&self, input: &Array2<f64>) -> CondensedTree {
+        let mut hdbscan = HDbscan {
+            eps: f64::MAX,
+            alpha: self.parameters.alpha,
+            min_samples: self.parameters.min_samples,
+            min_cluster_size: self.parameters.min_cluster_size,
+            metric: self.metric.clone(),
+            boruvka: self.boruvka,
+        };
+
+        hdbscan.compute_condensed(input)
+    }
+}
+
+impl<M: Metric<f64> + Clone + Sync> ClusteringStrategy for HdbscanStrategy<M> {
+    fn cluster(&self, groups: &[PhotoGroup], input: &Array2<f64>) -> Tree {
+        let selection = self.parameters.cluster_selection;
+        build_tree(groups, selection, &self.compute_condensed(input))
+    }
+}
+
+/// A root with a node per cluster, each of them selected, and one for the points in no
+/// cluster.
+fn flat_tree(groups: &[PhotoGroup], clusters: Vec<Vec<usize>>, noise: Vec<usize>) -> Tree {
+    let to_child = |idx: usize| Rc::new(RefCell::new(group_to_tree(&groups[idx])));
+
+    let mut children: LinkedList<_> = clusters
+        .into_iter()
+        .map(|members| {
+            let leaves = members.into_iter().map(to_child).collect();
+            let details = NodeDetails {
+                selected: true,
+                ..NodeDetails::default()
+            };
+            Rc::new(RefCell::new(Tree::Node(NumberOfLeaves(0), leaves, details)))
+        })
+        .collect();
+    if !noise.is_empty() {
+        children.push_back(unclustered_node(noise.into_iter().map(to_child).collect()));
+    }
+
+    let root = Rc::new(RefCell::new(Tree::Node(
+        NumberOfLeaves(0),
+        children,
+        NodeDetails::default(),
+    )));
+    recount(&root);
+    Rc::try_unwrap(root)
+        .ok()
+        .expect("root is not shared")
+        .into_inner()
+}
+
+/// Clusters in a stable order, by their first point.
+fn sorted_clusters(clusters: impl IntoIterator<Item = Vec<usize>>) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<Vec<usize>> = clusters
+        .into_iter()
+        .map(|mut members| {
+            members.sort();
+            members
+        })
+        .collect();
+    clusters.sort();
+    clusters
+}
+
+struct DbscanStrategy<M> {
+    eps_meters: f64,
+    min_samples: usize,
+    metric: M,
+}
+
+impl<M: Metric<f64> + Clone + Sync> ClusteringStrategy for DbscanStrategy<M> {
+    fn cluster(&self, groups: &[PhotoGroup], input: &Array2<f64>) -> Tree {
+        let mut dbscan = Dbscan {
+            eps: self.eps_meters,
+            min_samples: self.min_samples,
+            metric: self.metric.clone(),
+        };
+        let (clusters, noise) = dbscan.fit(input);
+
+        flat_tree(groups, sorted_clusters(clusters.into_values()), noise)
+    }
+}
+
+struct OpticsStrategy<M> {
+    eps_meters: f64,
+    min_samples: usize,
+    metric: M,
+}
+
+impl<M: Metric<f64> + Clone + Sync> ClusteringStrategy for OpticsStrategy<M> {
+    fn cluster(&self, groups: &[PhotoGroup], input: &Array2<f64>) -> Tree {
+        let mut optics = Optics::new(self.eps_meters, self.min_samples, self.metric.clone());
+        let (clusters, noise) = optics.fit(input);
+
+        flat_tree(groups, sorted_clusters(clusters.into_values()), noise)
+    }
+}
+
+struct GreedyRadiusStrategy<M> {
+    radius_meters: f64,
+    metric: M,
+}
+
+impl<M: Metric<f64>> ClusteringStrategy for GreedyRadiusStrategy<M> {
+    fn cluster(&self, groups: &[PhotoGroup], input: &Array2<f64>) -> Tree {
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        for (i, point) in input.rows().into_iter().enumerate() {
+            let joined = clusters.iter_mut().find(|members| {
+                let seed = input.row(members[0]);
+                self.metric.distance(&seed, &point) <= self.radius_meters
+            });
+            match joined {
+                Some(members) => members.push(i),
+                None => clusters.push(vec![i]),
+            }
+        }
+
+        let (clusters, noise): (Vec<_>, Vec<_>) =
+            clusters.into_iter().partition(|members| members.len() > 1);
+        flat_tree(groups, clusters, noise.into_iter().flatten().collect())
+    }
+}
+
+fn with_metric<M>(
+    parameters: &ClusteringParameters,
+    metric: M,
+    boruvka: bool,
+) -> Box<dyn ClusteringStrategy>
+where
+    M: Metric<f64> + Clone + Sync + 'static,
+{
+    match parameters.algorithm {
+        ClusteringAlgorithm::Hdbscan => Box::new(HdbscanStrategy {
+            parameters: parameters.clone(),
+            metric,
+            boruvka,
+        }),
+        ClusteringAlgorithm::Dbscan { eps_meters } => Box::new(DbscanStrategy {
+            eps_meters,
+            min_samples: parameters.min_samples,
+            metric,
+        }),
+        ClusteringAlgorithm::Optics { eps_meters } => Box::new(OpticsStrategy {
+            eps_meters,
+            min_samples: parameters.min_samples,
+            metric,
+        }),
+        ClusteringAlgorithm::GreedyRadius { radius_meters } => Box::new(GreedyRadiusStrategy {
+            radius_meters,
+            metric,
+        }),
+    }
+}
+
+/// The strategy `parameters` select, measuring distances the way they ask.
+pub fn clustering_strategy(parameters: &ClusteringParameters) -> Box<dyn ClusteringStrategy> {
+    match parameters.metric {
+        DistanceMetric::Distance => with_metric(parameters, Haversine::default(), true),
+        DistanceMetric::SpaceTime { meters_per_hour } => {
+            with_metric(parameters, SpaceTime { meters_per_hour }, false)
+        }
+    }
+}
@@ -7,8 +7,8 @@ fn get_convex_hull(children: &LinkedList<Box<Tree2>>) -> ConvexHull2 {
     let multi_point: MultiPoint<f64> = children
         .iter()
         .flat_map(|t| match &**t {
-            Tree2::Node(convex_hull, _, _) => convex_hull.0.clone(),
-            Tree2::Leaf(_, coord) => LinkedList::from([*coord]),
+            Tree2::Node(convex_hull, _, _, _) => convex_hull.0.clone(),
+            Tree2::Leaf(_, coord, _) => LinkedList::from([*coord]),
         })
         .map(|f| point! {x: f.lat, y: f.lon})
         .collect();
@@ -23,18 +23,35 @@ fn get_convex_hull(children: &LinkedList<Box<Tree2>>) -> ConvexHull2 {
     ConvexHull2(ch)
 }
 
+fn get_date_range(children: &LinkedList<Box<Tree2>>) -> Option<DateRange> {
+    children
+        .iter()
+        .filter_map(|t| match &**t {
+            Tree2::Node(_, _, _, details) => details.date_range,
+            Tree2::Leaf(_, _, details) => details.timestamp.map(|timestamp| DateRange {
+                first: timestamp,
+                last: timestamp,
+            }),
+        })
+        .reduce(DateRange::union)
+}
+
 pub fn convert_tree(tree: &mut Tree) -> Tree2 {
     match tree {
-        Tree::Node(n, l) => {
+        Tree::Node(n, l, details) => {
             let children = l
                 .iter()
                 .map(|r| Box::new(convert_tree(&mut *r.borrow_mut())))
                 .collect();
 
             let convex_hull = get_convex_hull(&children);
+            let details = NodeDetails {
+                date_range: get_date_range(&children),
+                ..details.clone()
+            };
 
-            return Tree2::Node(convex_hull, *n, children);
+            return Tree2::Node(convex_hull, *n, children, details);
         }
-        Tree::Leaf(f, c) => Tree2::Leaf(f.clone(), *c),
+        Tree::Leaf(f, c, d) => Tree2::Leaf(f.clone(), *c, d.clone()),
     }
 }
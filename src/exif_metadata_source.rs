@@ -0,0 +1,523 @@
+use std::path::Path;
+
+use exif::{DateTime, Field, In, Tag, Value};
+
+use crate::capture_time::parse_utc_offset;
+use crate::metadata_source::*;
+use crate::raw_containers::*;
+use crate::types::*;
+
+fn convert_min_to_decimal(deg: f64, min: f64, sec: f64) -> f64 {
+    deg + (min / 60.) + (sec / 3600.)
+}
+
+fn get_field(fields: &[Field], tag: Tag) -> Option<&Field> {
+    fields
+        .iter()
+        .find(|field| field.tag == tag && field.ifd_num == In::PRIMARY)
+}
+
+/// A missing reference is taken as north / east, which is what most readers assume.
+fn get_ref_sign(field: Option<&Field>, positive: u8, negative: u8) -> Result<f64, GpsError> {
+    let first_char = match field.map(|f| &f.value) {
+        None => return Ok(1.0),
+        Some(Value::Ascii(ascii)) => ascii.first().and_then(|s| s.first()).copied(),
+        Some(_) => return Err(GpsError::UnexpectedRef(String::from("non-ASCII value"))),
+    };
+
+    match first_char.map(|c| c.to_ascii_uppercase()) {
+        None | Some(0) | Some(b' ') => Ok(1.0),
+        Some(c) if c == positive => Ok(1.0),
+        Some(c) if c == negative => Ok(-1.0),
+        Some(c) => Err(GpsError::UnexpectedRef((c as char).to_string())),
+    }
+}
+
+/// Minutes and seconds written with a zero denominator are unused and count as zero;
+/// degrees without a denominator are not a number.
+fn to_components(value: &Value) -> Result<Vec<f64>, GpsError> {
+    let ratio = |i: usize, num: f64, denom: f64| {
+        if denom != 0. {
+            num / denom
+        } else if i == 0 {
+            f64::NAN
+        } else {
+            0.
+        }
+    };
+
+    let components: Vec<f64> = match value {
+        Value::Rational(v) => v
+            .iter()
+            .enumerate()
+            .map(|(i, r)| ratio(i, r.num as f64, r.denom as f64))
+            .collect(),
+        Value::SRational(v) => v
+            .iter()
+            .enumerate()
+            .map(|(i, r)| ratio(i, r.num as f64, r.denom as f64))
+            .collect(),
+        Value::Float(v) => v.iter().map(|f| *f as f64).collect(),
+        Value::Double(v) => v.clone(),
+        _ => return Err(GpsError::UnexpectedValueType),
+    };
+
+    if components.iter().any(|c| !c.is_finite()) {
+        return Err(GpsError::NotANumber);
+    }
+
+    Ok(components)
+}
+
+fn to_decimal(value: &Value, sign: f64) -> Result<f64, GpsError> {
+    let components = to_components(value)?;
+    let (deg, min, sec) = match components.as_slice() {
+        [] => return Err(GpsError::MissingComponents),
+        [deg] => (*deg, 0., 0.),
+        [deg, min] => (*deg, *min, 0.),
+        [deg, min, sec, ..] => (*deg, *min, *sec),
+    };
+
+    let decimal = convert_min_to_decimal(deg.abs(), min.abs(), sec.abs());
+
+    // Signed values already carry the hemisphere.
+    if components.iter().any(|c| *c < 0.) {
+        Ok(-decimal)
+    } else {
+        Ok(sign * decimal)
+    }
+}
+
+fn extract_coord(fields: &[Field]) -> Result<Option<Coord>, GpsError> {
+    let (Some(lat), Some(lon)) = (
+        get_field(fields, Tag::GPSLatitude),
+        get_field(fields, Tag::GPSLongitude),
+    ) else {
+        return Ok(None);
+    };
+
+    let lat_sign = get_ref_sign(get_field(fields, Tag::GPSLatitudeRef), b'N', b'S')?;
+    let lon_sign = get_ref_sign(get_field(fields, Tag::GPSLongitudeRef), b'E', b'W')?;
+
+    let lat = to_decimal(&lat.value, lat_sign)?;
+    let lon = to_decimal(&lon.value, lon_sign)?;
+
+    if lat.abs() > 90. || lon.abs() > 180. {
+        return Err(GpsError::OutOfRange(lat, lon));
+    }
+
+    Ok(Some(Coord { lat, lon }))
+}
+
+fn extract_date_time(fields: &[Field]) -> Option<MyDateTime> {
+    let date_time_field = get_field(fields, Tag::DateTimeOriginal)?;
+
+    let datetime = match &date_time_field.value {
+        Value::Ascii(ascii) => {
+            let f = ascii.first()?;
+            DateTime::from_ascii(f).ok()
+        }
+        _ => None,
+    }?;
+
+    Some(MyDateTime::from(datetime))
+}
+
+fn get_ascii(fields: &[Field], tag: Tag) -> Option<&str> {
+    match &get_field(fields, tag)?.value {
+        Value::Ascii(ascii) => std::str::from_utf8(ascii.first()?).ok(),
+        _ => None,
+    }
+}
+
+/// Zone of `DateTimeOriginal`, written by cameras since EXIF 2.31.
+fn extract_utc_offset(fields: &[Field]) -> Option<i64> {
+    get_ascii(fields, Tag::OffsetTimeOriginal)
+        .or_else(|| get_ascii(fields, Tag::OffsetTime))
+        .and_then(parse_utc_offset)
+}
+
+/// UTC time of the GPS fix, usually within seconds of the capture.
+fn extract_utc_date_time(fields: &[Field]) -> Option<MyDateTime> {
+    let date: Vec<&str> = get_ascii(fields, Tag::GPSDateStamp)?.split(':').collect();
+    let time = to_components(&get_field(fields, Tag::GPSTimeStamp)?.value).ok()?;
+    let [hour, minute, second] = time.as_slice() else {
+        return None;
+    };
+
+    Some(MyDateTime {
+        year: date.first()?.trim().parse().ok()?,
+        month: date.get(1)?.trim().parse().ok()?,
+        day: date.get(2)?.trim().parse().ok()?,
+        hour: *hour as u8,
+        minute: *minute as u8,
+        second: *second as u8,
+    })
+}
+
+fn get_text(fields: &[Field], tag: Tag) -> Option<String> {
+    let text = get_ascii(fields, tag)?.trim_matches(['\0', ' ']);
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn get_uint(fields: &[Field], tag: Tag) -> Option<u32> {
+    get_field(fields, tag)?.value.get_uint(0)
+}
+
+fn extract_altitude(fields: &[Field]) -> Option<f64> {
+    let altitude = *to_components(&get_field(fields, Tag::GPSAltitude)?.value)
+        .ok()?
+        .first()?;
+    let below_sea_level = matches!(
+        get_field(fields, Tag::GPSAltitudeRef).map(|f| &f.value),
+        Some(Value::Byte(reference)) if reference.first() == Some(&1)
+    );
+
+    Some(if below_sea_level { -altitude } else { altitude })
+}
+
+fn extract_properties(fields: &[Field]) -> PhotoProperties {
+    PhotoProperties {
+        make: get_text(fields, Tag::Make),
+        model: get_text(fields, Tag::Model),
+        lens: get_text(fields, Tag::LensModel),
+        width: get_uint(fields, Tag::PixelXDimension).or_else(|| get_uint(fields, Tag::ImageWidth)),
+        height: get_uint(fields, Tag::PixelYDimension)
+            .or_else(|| get_uint(fields, Tag::ImageLength)),
+        orientation: get_uint(fields, Tag::Orientation).map(|o| o as u16),
+        altitude: extract_altitude(fields),
+    }
+}
+
+/// TIFF based raws (CR2, NEF, ARW, DNG) are read like any other TIFF; ORF, RAF and CR3
+/// need their container unwrapped first.
+fn read_fields(path: &Path) -> Result<Vec<Field>, SkipReason> {
+    if has_extension(path, &["orf"]) {
+        return read_orf(path);
+    }
+    if has_extension(path, &["raf"]) {
+        return read_raf(path);
+    }
+    if has_extension(path, &["cr3"]) {
+        return read_cr3(path);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let mut bufreader = std::io::BufReader::new(&file);
+    let exifreader = exif::Reader::new();
+    match exifreader.read_from_container(&mut bufreader) {
+        Ok(exif) => Ok(exif.fields().cloned().collect()),
+        // A valid image that simply carries no EXIF block.
+        Err(exif::Error::NotFound(_)) => Ok(Vec::new()),
+        Err(e) => Err(SkipReason::from(e)),
+    }
+}
+
+/// Reads GPS position and capture time from the EXIF block embedded in the image itself.
+pub struct ExifMetadataSource {}
+
+impl MetadataSource for ExifMetadataSource {
+    fn extract(&self, path: &Path) -> Result<Option<PhotoMetadata>, SkipReason> {
+        if !has_extension(path, &IMAGE_EXTENSIONS) {
+            return Ok(None);
+        }
+
+        let fields = read_fields(path)?;
+        let (coord, gps_error) = match extract_coord(&fields) {
+            Ok(coord) => (coord, None),
+            Err(e) => (None, Some(e)),
+        };
+
+        Ok(Some(PhotoMetadata {
+            coord,
+            date_time: extract_date_time(&fields),
+            utc_offset_seconds: extract_utc_offset(&fields),
+            utc_date_time: extract_utc_date_time(&fields),
+            gps_error,
+            properties: extract_properties(&fields),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::test_tiff::*;
+
+    /// Every file holds 52°13'5" N, 21°0'30" E taken at 2023-07-14 18:30:05.
+    fn assert_metadata(path: &Path) {
+        let name = path.display();
+        let metadata = ExifMetadataSource {}
+            .extract(path)
+            .unwrap_or_else(|e| panic!("{name}: {e:?}"))
+            .unwrap_or_else(|| panic!("{name}: no metadata"));
+
+        let coord = metadata
+            .coord
+            .unwrap_or_else(|| panic!("{name}: no position"));
+        assert!(
+            (coord.lat - 52.218056).abs() < 1e-6,
+            "{name}: {}",
+            coord.lat
+        );
+        assert!(
+            (coord.lon - 21.008333).abs() < 1e-6,
+            "{name}: {}",
+            coord.lon
+        );
+
+        let expected = MyDateTime {
+            year: 2023,
+            month: 7,
+            day: 14,
+            hour: 18,
+            minute: 30,
+            second: 5,
+        };
+        assert_eq!(metadata.date_time, Some(expected), "{name}");
+    }
+
+    fn assert_fixture(name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        assert_metadata(&path);
+    }
+
+    /// IFD0 of a camera, pointing to the Exif and GPS IFDs.
+    fn camera_ifd0() -> Ifd {
+        Ifd {
+            entries: vec![
+                (0x010f, TiffValue::Ascii("Test")),
+                (0x0110, TiffValue::Ascii("Fixture")),
+            ],
+            children: vec![(0x8769, vec![exif_ifd()]), (0x8825, vec![gps_ifd()])],
+            ..Ifd::default()
+        }
+    }
+
+    fn thumbnail_ifd() -> Ifd {
+        Ifd {
+            entries: vec![
+                (0x0103, TiffValue::Short(vec![6])),
+                (0x0201, TiffValue::Long(vec![0])),
+                (0x0202, TiffValue::Long(vec![0])),
+            ],
+            ..Ifd::default()
+        }
+    }
+
+    #[test]
+    fn reads_cr2() {
+        // IFD0, thumbnail, preview and raw image chained, the raw one also pointed to by
+        // the CR2 header after the TIFF one.
+        let mut ifd0 = camera_ifd0();
+        ifd0.next = Some(Box::new(Ifd {
+            next: Some(Box::new(Ifd {
+                next: Some(Box::new(raw_image_ifd())),
+                ..raw_image_ifd()
+            })),
+            ..thumbnail_ifd()
+        }));
+        let tiff = Tiff {
+            big_endian: false,
+            magic: [0x2a, 0x00],
+            header_extension: vec![b'C', b'R', 2, 0, 0, 0, 0, 0],
+            ifd0,
+        };
+        let (mut data, chain) = tiff.build();
+        assert_eq!(chain.len(), 4);
+        data[12..16].copy_from_slice(&chain[3].to_le_bytes());
+
+        let dir = TestDir::new();
+        assert_metadata(&dir.write("IMG_0001.CR2", data));
+    }
+
+    #[test]
+    fn reads_nef() {
+        // Big endian, with the preview and the raw image in SubIFDs.
+        let mut ifd0 = camera_ifd0();
+        ifd0.children
+            .push((0x014a, vec![thumbnail_ifd(), raw_image_ifd()]));
+        let tiff = Tiff {
+            big_endian: true,
+            magic: [0x00, 0x2a],
+            header_extension: Vec::new(),
+            ifd0,
+        };
+
+        let dir = TestDir::new();
+        assert_metadata(&dir.write("DSC_0001.NEF", tiff.build().0));
+    }
+
+    #[test]
+    fn reads_arw() {
+        // The raw image in a SubIFD, the thumbnail chained as IFD1.
+        let mut ifd0 = camera_ifd0();
+        ifd0.children.push((0x014a, vec![raw_image_ifd()]));
+        ifd0.next = Some(Box::new(thumbnail_ifd()));
+        let tiff = Tiff {
+            big_endian: false,
+            magic: [0x2a, 0x00],
+            header_extension: Vec::new(),
+            ifd0,
+        };
+
+        let dir = TestDir::new();
+        assert_metadata(&dir.write("DSC00001.ARW", tiff.build().0));
+    }
+
+    #[test]
+    fn reads_dng() {
+        let mut ifd0 = camera_ifd0();
+        ifd0.entries
+            .push((0xc612, TiffValue::Byte(vec![1, 4, 0, 0])));
+        ifd0.children.push((0x014a, vec![raw_image_ifd()]));
+        let tiff = Tiff {
+            big_endian: false,
+            magic: [0x2a, 0x00],
+            header_extension: Vec::new(),
+            ifd0,
+        };
+
+        let dir = TestDir::new();
+        assert_metadata(&dir.write("IMG_0001.dng", tiff.build().0));
+    }
+
+    #[test]
+    fn reads_orf() {
+        let mut ifd0 = camera_ifd0();
+        ifd0.entries.extend(raw_image_ifd().entries);
+        let tiff = Tiff {
+            big_endian: false,
+            magic: *b"RO",
+            header_extension: Vec::new(),
+            ifd0,
+        };
+
+        let dir = TestDir::new();
+        assert_metadata(&dir.write("P7140001.ORF", tiff.build().0));
+    }
+
+    #[test]
+    fn reads_raf() {
+        assert_fixture("gps.raf");
+    }
+
+    #[test]
+    fn reads_cr3() {
+        assert_fixture("gps.cr3");
+    }
+
+    fn rationals(values: &[(u32, u32)]) -> Value {
+        Value::Rational(
+            values
+                .iter()
+                .map(|&(num, denom)| exif::Rational { num, denom })
+                .collect(),
+        )
+    }
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn ascii(s: &str) -> Value {
+        Value::Ascii(vec![s.as_bytes().to_vec()])
+    }
+
+    #[test]
+    fn signed_rationals_carry_the_hemisphere() {
+        let value = Value::SRational(vec![
+            exif::SRational { num: -52, denom: 1 },
+            exif::SRational { num: 13, denom: 1 },
+            exif::SRational { num: 5, denom: 1 },
+        ]);
+        let decimal = to_decimal(&value, 1.0).unwrap();
+        assert!((decimal + 52.218056).abs() < 1e-6, "{decimal}");
+    }
+
+    #[test]
+    fn takes_a_missing_ref_as_north_and_east() {
+        let fields = [
+            field(Tag::GPSLatitude, rationals(&[(52, 1), (13, 1), (5, 1)])),
+            field(Tag::GPSLongitude, rationals(&[(21, 1), (0, 1), (30, 1)])),
+        ];
+        let coord = extract_coord(&fields).unwrap().unwrap();
+        assert!((coord.lat - 52.218056).abs() < 1e-6, "{}", coord.lat);
+        assert!((coord.lon - 21.008333).abs() < 1e-6, "{}", coord.lon);
+    }
+
+    #[test]
+    fn rejects_an_unexpected_ref() {
+        let west = field(Tag::GPSLatitudeRef, ascii("W"));
+        assert!(matches!(
+            get_ref_sign(Some(&west), b'N', b'S'),
+            Err(GpsError::UnexpectedRef(c)) if c == "W"
+        ));
+
+        let short = field(Tag::GPSLatitudeRef, Value::Short(vec![1]));
+        assert!(matches!(
+            get_ref_sign(Some(&short), b'N', b'S'),
+            Err(GpsError::UnexpectedRef(_))
+        ));
+
+        let south = field(Tag::GPSLatitudeRef, ascii("s"));
+        assert_eq!(get_ref_sign(Some(&south), b'N', b'S').unwrap(), -1.0);
+    }
+
+    #[test]
+    fn reads_fewer_than_three_components() {
+        let decimal = to_decimal(&rationals(&[(52, 1), (13, 1)]), 1.0).unwrap();
+        assert!((decimal - 52.216667).abs() < 1e-6, "{decimal}");
+        assert_eq!(to_decimal(&rationals(&[(52, 1)]), -1.0).unwrap(), -52.0);
+        assert!(matches!(
+            to_decimal(&rationals(&[]), 1.0),
+            Err(GpsError::MissingComponents)
+        ));
+    }
+
+    #[test]
+    fn rejects_an_unexpected_value_type() {
+        assert!(matches!(
+            to_decimal(&ascii("52.2"), 1.0),
+            Err(GpsError::UnexpectedValueType)
+        ));
+    }
+
+    #[test]
+    fn counts_minutes_and_seconds_without_denominator_as_zero() {
+        let value = rationals(&[(52, 1), (13, 0), (5, 0)]);
+        assert_eq!(to_decimal(&value, 1.0).unwrap(), 52.0);
+
+        let value = rationals(&[(52, 0), (13, 1), (5, 1)]);
+        assert!(matches!(to_decimal(&value, 1.0), Err(GpsError::NotANumber)));
+    }
+
+    #[test]
+    fn rejects_positions_out_of_range() {
+        let fields = [
+            field(Tag::GPSLatitude, rationals(&[(91, 1), (0, 1), (0, 1)])),
+            field(Tag::GPSLongitude, rationals(&[(21, 1), (0, 1), (30, 1)])),
+        ];
+        assert!(matches!(
+            extract_coord(&fields),
+            Err(GpsError::OutOfRange(lat, _)) if lat == 91.0
+        ));
+
+        let fields = [
+            field(Tag::GPSLatitude, rationals(&[(52, 1), (0, 1), (0, 1)])),
+            field(Tag::GPSLongitudeRef, ascii("W")),
+            field(Tag::GPSLongitude, rationals(&[(180, 1), (30, 1), (0, 1)])),
+        ];
+        assert!(matches!(
+            extract_coord(&fields),
+            Err(GpsError::OutOfRange(_, lon)) if lon == -180.5
+        ));
+    }
+}
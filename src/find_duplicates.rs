@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::types::*;
+
+/// Groups files, given as `(filepath, content hash)`, with equal contents. Maps every extra
+/// copy to the copy that is kept, the one with the smallest path.
+pub fn find_duplicates<'a, I>(files: I) -> HashMap<&'a str, &'a str>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut groups: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (filepath, hash) in files {
+        groups.entry(hash).or_default().push(filepath);
+    }
+
+    let mut duplicates = HashMap::new();
+    for mut group in groups.into_values() {
+        group.sort();
+        group.dedup();
+        if let Some((kept, copies)) = group.split_first() {
+            for copy in copies {
+                duplicates.insert(*copy, *kept);
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Points `duplicate_of` of every extra copy among `photos` at the kept one.
+pub fn mark_duplicates(photos: &mut [Photo]) {
+    let duplicates: HashMap<String, Filepath> = find_duplicates(
+        photos
+            .iter()
+            .map(|photo| (photo.filepath.0.as_str(), photo.content_hash.as_str())),
+    )
+    .into_iter()
+    .map(|(copy, kept)| (copy.to_string(), Filepath(kept.to_string())))
+    .collect();
+
+    for photo in photos {
+        photo.details.duplicate_of = duplicates.get(&photo.filepath.0).cloned();
+    }
+}
@@ -0,0 +1,9 @@
+use std::path::{Path, PathBuf};
+
+/// The root `path` lies in. With nested roots, the innermost one.
+pub fn find_root<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a PathBuf> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+}
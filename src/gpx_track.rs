@@ -0,0 +1,201 @@
+use std::io::{self, Error};
+
+use crate::capture_time::iso8601_offset_seconds;
+use crate::types::*;
+
+struct TrackPoint {
+    /// Seconds since the Unix epoch, UTC.
+    time: i64,
+    coord: Coord,
+}
+
+fn get_attribute(tag: &str, name: &str) -> Option<f64> {
+    [format!(" {}=\"", name), format!(" {}='", name)]
+        .iter()
+        .find_map(|prefix| {
+            let start = tag.find(prefix.as_str())? + prefix.len();
+            let quote = prefix.chars().last()?;
+            tag[start..].split(quote).next()?.trim().parse().ok()
+        })
+}
+
+fn get_time(trkpt: &str) -> Option<i64> {
+    let start = trkpt.find("<time>")? + "<time>".len();
+    let value = trkpt[start..].split('<').next()?.trim();
+    let date_time = MyDateTime::from_iso8601(value)?;
+    let offset = iso8601_offset_seconds(value).unwrap_or(0);
+
+    Some(date_time.to_unix_timestamp()? - offset)
+}
+
+fn parse_track_points(gpx: &str) -> Vec<TrackPoint> {
+    gpx.split("<trkpt")
+        .skip(1)
+        .filter_map(|trkpt| {
+            let trkpt = trkpt.split("</trkpt>").next()?;
+            let tag = trkpt.split('>').next()?;
+            let coord = Coord {
+                lat: get_attribute(tag, "lat")?,
+                lon: get_attribute(tag, "lon")?,
+            };
+
+            Some(TrackPoint {
+                time: get_time(trkpt)?,
+                coord,
+            })
+        })
+        .collect()
+}
+
+/// Track points from one or more GPX logs, used to geotag photos by capture time.
+pub struct GpxTrack {
+    points: Vec<TrackPoint>,
+    camera_clock_offset_seconds: i64,
+    max_gap_seconds: i64,
+}
+
+impl GpxTrack {
+    pub fn load(geotagging: &Geotagging) -> io::Result<GpxTrack> {
+        let mut points = Vec::new();
+        for Filepath(gpx_file) in geotagging.gpx_files.iter() {
+            let content = std::fs::read_to_string(gpx_file)?;
+            let mut parsed = parse_track_points(&content);
+            if parsed.is_empty() {
+                let msg = format!("No timestamped track points in {:?}", gpx_file);
+                return Err(Error::other(msg));
+            }
+            points.append(&mut parsed);
+        }
+
+        points.sort_by_key(|point| point.time);
+
+        Ok(GpxTrack {
+            points,
+            camera_clock_offset_seconds: geotagging.camera_clock_offset_seconds,
+            max_gap_seconds: geotagging.max_gap_seconds,
+        })
+    }
+
+    /// Position at the time the photo was taken. The camera clock offset only applies when
+    /// the photo does not tell its time in UTC.
+    pub fn locate(&self, metadata: &PhotoMetadata) -> Option<Coord> {
+        let time = metadata.unix_timestamp().or_else(|| {
+            let date_time = metadata.date_time?;
+            Some(date_time.to_unix_timestamp()? - self.camera_clock_offset_seconds)
+        })?;
+        self.locate_at(time)
+    }
+
+    /// Position at `time`, in seconds since the Unix epoch. Between two track points no
+    /// more than `max_gap_seconds` apart the position is interpolated linearly. Across a
+    /// longer gap, and before the first or after the last point, the nearest point is used
+    /// if it is no more than `max_gap_seconds` away.
+    fn locate_at(&self, time: i64) -> Option<Coord> {
+        let idx = self.points.partition_point(|point| point.time < time);
+
+        let after = self.points.get(idx);
+        let before = idx.checked_sub(1).and_then(|i| self.points.get(i));
+
+        match (before, after) {
+            (_, Some(after)) if after.time == time => Some(after.coord),
+            (Some(before), Some(after)) if after.time - before.time <= self.max_gap_seconds => {
+                let ratio = (time - before.time) as f64 / (after.time - before.time) as f64;
+                Some(Coord {
+                    lat: before.coord.lat + ratio * (after.coord.lat - before.coord.lat),
+                    lon: before.coord.lon + ratio * (after.coord.lon - before.coord.lon),
+                })
+            }
+            (before, after) => {
+                let nearest = before
+                    .into_iter()
+                    .chain(after)
+                    .min_by_key(|point| (point.time - time).abs())?;
+                ((nearest.time - time).abs() <= self.max_gap_seconds).then_some(nearest.coord)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2023-07-14 18:00:00 UTC.
+    const START: i64 = 1_689_357_600;
+
+    const GPX: &str = r#"<gpx><trk><trkseg>
+        <trkpt lat="52.0" lon="21.0"><time>2023-07-14T18:00:00Z</time></trkpt>
+        <trkpt lat='52.1' lon='21.2'><time>2023-07-14T20:05:00+02:00</time></trkpt>
+        <trkpt lat="53.0" lon="22.0"><time>2023-07-14T19:05:00Z</time></trkpt>
+    </trkseg></trk></gpx>"#;
+
+    fn track(camera_clock_offset_seconds: i64) -> GpxTrack {
+        GpxTrack {
+            points: parse_track_points(GPX),
+            camera_clock_offset_seconds,
+            max_gap_seconds: 600,
+        }
+    }
+
+    fn assert_coord(coord: Option<Coord>, lat: f64, lon: f64) {
+        let coord = coord.expect("no position");
+        assert!((coord.lat - lat).abs() < 1e-9, "{coord:?}");
+        assert!((coord.lon - lon).abs() < 1e-9, "{coord:?}");
+    }
+
+    #[test]
+    fn parses_track_points_in_any_zone() {
+        let times: Vec<i64> = parse_track_points(GPX).iter().map(|p| p.time).collect();
+        assert_eq!(times, vec![START, START + 300, START + 3900]);
+    }
+
+    #[test]
+    fn interpolates_between_track_points() {
+        let track = track(0);
+        assert_coord(track.locate_at(START), 52.0, 21.0);
+        assert_coord(track.locate_at(START + 60), 52.02, 21.04);
+    }
+
+    #[test]
+    fn uses_the_nearest_end_of_the_track_within_the_gap() {
+        let track = track(0);
+        assert_coord(track.locate_at(START - 600), 52.0, 21.0);
+        assert_eq!(track.locate_at(START - 601).map(|c| c.lat), None);
+        assert_coord(track.locate_at(START + 4500), 53.0, 22.0);
+        assert_eq!(track.locate_at(START + 4501).map(|c| c.lat), None);
+    }
+
+    #[test]
+    fn does_not_interpolate_across_a_long_gap() {
+        let track = track(0);
+        assert_coord(track.locate_at(START + 301), 52.1, 21.2);
+        assert_coord(track.locate_at(START + 3899), 53.0, 22.0);
+        assert_eq!(track.locate_at(START + 2100).map(|c| c.lat), None);
+    }
+
+    #[test]
+    fn shifts_the_camera_clock_only_for_photos_without_zone() {
+        let track = track(7200);
+        let date_time = MyDateTime {
+            year: 2023,
+            month: 7,
+            day: 14,
+            hour: 20,
+            minute: 5,
+            second: 0,
+        };
+
+        let without_zone = PhotoMetadata {
+            date_time: Some(date_time),
+            ..Default::default()
+        };
+        assert_coord(track.locate(&without_zone), 52.1, 21.2);
+
+        let in_utc = PhotoMetadata {
+            date_time: Some(date_time),
+            utc_offset_seconds: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(track.locate(&in_utc).map(|c| c.lat), None);
+    }
+}
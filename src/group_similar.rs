@@ -0,0 +1,153 @@
+use std::collections::{BTreeSet, HashMap};
+
+use geoutils::Location;
+
+use crate::types::*;
+
+/// Bursts and near-duplicates are shot from one place; GPS jitter stays well below this.
+const BURST_MAX_METERS: f64 = 50.;
+
+/// Disjoint sets of photo indices, remembering which sets were joined by capture time.
+struct Sets {
+    parents: Vec<usize>,
+    bursts: Vec<bool>,
+}
+
+impl Sets {
+    fn new(len: usize) -> Sets {
+        Sets {
+            parents: (0..len).collect(),
+            bursts: vec![false; len],
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        self.parents[i] = root;
+        root
+    }
+
+    fn join(&mut self, i: usize, j: usize, burst: bool) {
+        let (i, j) = (self.find(i), self.find(j));
+        if i != j {
+            self.parents[j] = i;
+            self.bursts[i] |= self.bursts[j];
+        }
+        self.bursts[i] |= burst;
+    }
+}
+
+/// Bits `part` of `parts` equal slices of a 64 bit hash.
+fn hash_part(hash: u64, part: usize, parts: usize) -> u64 {
+    let (start, end) = (64 * part / parts, 64 * (part + 1) / parts);
+    let mask = u64::MAX >> (64 - (end - start));
+    (hash >> start) & mask
+}
+
+/// Pairs `(i, j)` of photos whose perceptual hashes differ in at most `max_distance` bits.
+/// Cut into `max_distance + 1` parts, two such hashes are equal in at least one of them, so
+/// only hashes sharing a part are compared.
+fn similar_hash_pairs(hashes: &[(usize, u64)], max_distance: u32) -> BTreeSet<(usize, usize)> {
+    let parts = (max_distance as usize + 1).min(64);
+    let mut buckets: HashMap<(usize, u64), Vec<(usize, u64)>> = HashMap::new();
+    for (i, hash) in hashes {
+        for part in 0..parts {
+            let key = (part, hash_part(*hash, part, parts));
+            buckets.entry(key).or_default().push((*i, *hash));
+        }
+    }
+
+    let mut pairs = BTreeSet::new();
+    for bucket in buckets.values() {
+        for (k, (i, hash1)) in bucket.iter().enumerate() {
+            for (j, hash2) in &bucket[k + 1..] {
+                if (hash1 ^ hash2).count_ones() <= max_distance {
+                    pairs.insert((*i, *j));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+fn distance_meters(c1: &Coord, c2: &Coord) -> f64 {
+    let fst = Location::new_const(c1.lat, c1.lon);
+    let snd = Location::new_const(c2.lat, c2.lon);
+    fst.haversine_distance_to(&snd).meters()
+}
+
+/// Puts near-duplicates, by perceptual hash, and bursts, by capture time and place, into
+/// shared groups, earliest photo first. Without `options` every photo is a group of its own.
+pub fn group_similar(photos: Vec<Photo>, options: Option<&SimilarityOptions>) -> Vec<PhotoGroup> {
+    let Some(options) = options else {
+        return photos
+            .into_iter()
+            .map(|photo| PhotoGroup {
+                photos: vec![photo],
+                kind: None,
+            })
+            .collect();
+    };
+
+    let mut sets = Sets::new(photos.len());
+
+    let hashes: Vec<(usize, u64)> = photos
+        .iter()
+        .enumerate()
+        .filter_map(|(i, photo)| photo.perceptual_hash.map(|hash| (i, hash)))
+        .collect();
+    // Similar looking photos from different places are not duplicates of each other.
+    for (i, j) in similar_hash_pairs(&hashes, options.max_hash_distance) {
+        if distance_meters(&photos[i].coord, &photos[j].coord) <= BURST_MAX_METERS {
+            sets.join(i, j, false);
+        }
+    }
+
+    let mut by_time: Vec<(i64, usize)> = photos
+        .iter()
+        .enumerate()
+        .filter_map(|(i, photo)| Some((photo.date_time?.to_unix_timestamp()?, i)))
+        .collect();
+    by_time.sort();
+    for pair in by_time.windows(2) {
+        let ((t1, i), (t2, j)) = (pair[0], pair[1]);
+        if t2 - t1 <= options.burst_seconds
+            && distance_meters(&photos[i].coord, &photos[j].coord) <= BURST_MAX_METERS
+        {
+            sets.join(i, j, true);
+        }
+    }
+
+    let mut groups: Vec<PhotoGroup> = Vec::new();
+    let mut group_of_set = HashMap::new();
+    for (i, photo) in photos.into_iter().enumerate() {
+        let set = sets.find(i);
+        let idx = *group_of_set.entry(set).or_insert_with(|| {
+            groups.push(PhotoGroup {
+                photos: vec![],
+                kind: None,
+            });
+            groups.len() - 1
+        });
+        groups[idx].photos.push(photo);
+    }
+
+    for (set, idx) in group_of_set {
+        let group = &mut groups[idx];
+        if group.photos.len() > 1 {
+            group.photos.sort_by(|p1, p2| {
+                (p1.date_time, &p1.filepath.0).cmp(&(p2.date_time, &p2.filepath.0))
+            });
+            group.kind = Some(if sets.bursts[set] {
+                GroupKind::Burst
+            } else {
+                GroupKind::NearDuplicates
+            });
+        }
+    }
+
+    groups
+}
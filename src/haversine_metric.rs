@@ -1,26 +1,66 @@
-use geoutils::Location;
 use ndarray::ArrayView1;
 use petal_neighbors::distance::Metric;
 
 use crate::types::Haversine;
 
+/// Mean Earth radius.
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
 impl Metric<f64> for Haversine {
-    /// Euclidean distance metric.
+    /// Great-circle distance in meters between `[lat, lon]` points in degrees.
     fn distance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
-        let snd = Location::new_const(x2[0], x2[1]);
-        let fst = Location::new_const(x1[0], x1[1]);
-        fst.haversine_distance_to(&snd).meters()
+        self.rdistance_to_distance(self.rdistance(x1, x2))
+    }
+
+    /// The haversine of the central angle, which grows with the distance and needs no
+    /// inverse trigonometry.
+    fn rdistance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        let (lat1, lat2) = (x1[0].to_radians(), x2[0].to_radians());
+        let half_dlat = (lat2 - lat1) / 2.;
+        let half_dlon = (x2[1] - x1[1]).to_radians() / 2.;
+
+        let h = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlon.sin().powi(2);
+        h.clamp(0., 1.)
     }
-    /// Euclidean reduce distance metric.
-    fn rdistance(&self, _x1: &ArrayView1<f64>, _x2: &ArrayView1<f64>) -> f64 {
-        todo!();
+
+    fn rdistance_to_distance(&self, d: f64) -> f64 {
+        2. * EARTH_RADIUS_METERS * d.sqrt().asin()
+    }
+
+    fn distance_to_rdistance(&self, d: f64) -> f64 {
+        let half_angle = (d / (2. * EARTH_RADIUS_METERS)).min(std::f64::consts::FRAC_PI_2);
+        half_angle.sin().powi(2)
     }
-    fn rdistance_to_distance(&self, _d: f64) -> f64 {
-        todo!();
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr1;
+
+    use super::*;
+
+    const WARSAW: [f64; 2] = [52.2297, 21.0122];
+    const ROME: [f64; 2] = [41.9028, 12.4964];
+    const NEW_YORK: [f64; 2] = [40.7128, -74.0060];
+    const SYDNEY: [f64; 2] = [-33.8688, 151.2093];
+
+    fn assert_distance(from: [f64; 2], to: [f64; 2], kilometers: f64) {
+        let (from, to) = (arr1(&from), arr1(&to));
+        let metric = Haversine::default();
+
+        let distance = metric.distance(&from.view(), &to.view());
+        assert!((distance / 1000. - kilometers).abs() < 1., "{distance} m");
+
+        let rdistance = metric.rdistance(&from.view(), &to.view());
+        assert!((metric.rdistance_to_distance(rdistance) - distance).abs() < 1e-6);
+        assert!((metric.distance_to_rdistance(distance) - rdistance).abs() < 1e-12);
     }
 
-    /// Euclidean reduce distance metric.
-    fn distance_to_rdistance(&self, _d: f64) -> f64 {
-        todo!();
+    #[test]
+    fn measures_city_pairs() {
+        assert_distance(WARSAW, ROME, 1315.);
+        assert_distance(WARSAW, NEW_YORK, 6854.);
+        assert_distance(NEW_YORK, SYDNEY, 15989.);
+        assert_distance(WARSAW, WARSAW, 0.);
     }
 }
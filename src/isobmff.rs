@@ -0,0 +1,102 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Top-level metadata boxes are normally a few hundred kilobytes; anything bigger is not
+/// worth reading.
+const MAX_TOP_LEVEL_BOX_SIZE: u64 = 64 * 1024 * 1024;
+
+struct BoxHeader {
+    box_type: [u8; 4],
+    header_size: u64,
+    size: u64,
+}
+
+fn read_box_header(file: &mut File, file_size: u64) -> io::Result<BoxHeader> {
+    let position = file.stream_position()?;
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+
+    let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+    let box_type = [header[4], header[5], header[6], header[7]];
+
+    let (header_size, size) = match size {
+        0 => (8, file_size - position),
+        1 => {
+            let mut large_size = [0u8; 8];
+            file.read_exact(&mut large_size)?;
+            (16, u64::from_be_bytes(large_size))
+        }
+        size => (8, size),
+    };
+
+    Ok(BoxHeader {
+        box_type,
+        header_size,
+        size,
+    })
+}
+
+/// Reads the payload of the first top-level box of the given type, skipping over
+/// everything else (usually a huge `mdat`) without reading it.
+pub fn read_top_level_box(path: &Path, box_type: &[u8; 4]) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut position = 0;
+    while position + 8 <= file_size {
+        file.seek(SeekFrom::Start(position))?;
+        let header = read_box_header(&mut file, file_size)?;
+        if header.size < header.header_size {
+            return Ok(None);
+        }
+
+        if &header.box_type == box_type {
+            let payload_size = header.size - header.header_size;
+            if payload_size > MAX_TOP_LEVEL_BOX_SIZE {
+                return Ok(None);
+            }
+            let mut payload = vec![0u8; payload_size as usize];
+            file.read_exact(&mut payload)?;
+            return Ok(Some(payload));
+        }
+
+        position += header.size;
+    }
+
+    Ok(None)
+}
+
+/// Returns `(type, payload)` of the boxes directly contained in `data`.
+pub fn children(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut result = Vec::new();
+    let mut rest = data;
+    while rest.len() >= 8 {
+        let size = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let box_type = &rest[4..8];
+        let (header_size, size) = match size {
+            0 => (8, rest.len()),
+            1 if rest.len() >= 16 => {
+                let large_size = u64::from_be_bytes(rest[8..16].try_into().unwrap());
+                (16, large_size as usize)
+            }
+            size => (8, size),
+        };
+        if size < header_size || size > rest.len() {
+            break;
+        }
+
+        result.push((box_type, &rest[header_size..size]));
+        rest = &rest[size..];
+    }
+    result
+}
+
+pub fn find_child<'a>(data: &'a [u8], box_type: &[u8]) -> Option<&'a [u8]> {
+    children(data)
+        .into_iter()
+        .find(|(t, _)| *t == box_type)
+        .map(|(_, payload)| payload)
+}
@@ -1,25 +1,25 @@
-use std::path::Path;
 use ndarray::Array2;
-use petal_clustering::HDbscan;
-use walkdir::WalkDir;
 
 use crate::types::*;
 
-use crate::build_tree::*;
+use crate::build_tree::add_unclustered;
+use crate::clustering_strategy::clustering_strategy;
 use crate::convert_tree::*;
-use crate::extract_filepath_location::*;
+use crate::find_duplicates::mark_duplicates;
+use crate::group_similar::group_similar;
+use crate::scan_directory::*;
 use crate::to_serde_tree::*;
 
 fn get_number_of_leaves(tree: &Tree2) -> usize {
     match tree {
-        Tree2::Node(_, n, _) => n.0,
-        Tree2::Leaf(_, _) => 1,
+        Tree2::Node(_, n, _, _) => n.0,
+        Tree2::Leaf(_, _, _) => 1,
     }
 }
 
 fn validate_tree(tree: &Tree2) {
     match tree {
-        Tree2::Node(_, n, children) => {
+        Tree2::Node(_, n, children, _) => {
             let calculated = children
                 .into_iter()
                 .fold(0, |accum, f| accum + get_number_of_leaves(f));
@@ -27,52 +27,76 @@ fn validate_tree(tree: &Tree2) {
                 panic!("Validation error");
             }
         }
-        Tree2::Leaf(_, _) => {}
+        Tree2::Leaf(_, _, _) => {}
     }
 }
 
-pub fn launch_pipeline_for_directory(path: &Path) -> Option<SerdeTree> {
-    let jpegs_with_geo: Vec<_> = WalkDir::new(path)
-        .into_iter()
-        .filter_map(extract_filepath_location)
-        .collect();
+/// Clusters `photos` into a tree, a group of similar photos counting as one point. Returns
+/// `None` when there are too few points to cluster, no more than `min_cluster_size` or
+/// `min_samples`.
+pub fn cluster_photos(
+    photos: Vec<Photo>,
+    similarity: Option<&SimilarityOptions>,
+    parameters: &ClusteringParameters,
+) -> Option<SerdeTree> {
+    let groups = group_similar(photos, similarity);
 
-    if jpegs_with_geo.len() < 5 {
+    // `SpaceTime` cannot tell how far a photo without capture time is from the others, so
+    // such photos are not clustered and go to the unclustered ones.
+    let (groups, undated): (Vec<_>, Vec<_>) = match parameters.metric {
+        DistanceMetric::SpaceTime { .. } => groups
+            .into_iter()
+            .partition(|group| group.photos[0].date_time.is_some()),
+        DistanceMetric::Distance => (groups, Vec::new()),
+    };
+    if groups.len() <= parameters.min_cluster_size.max(parameters.min_samples) {
         return None;
     }
 
-    let hdbscan_input_vector: Vec<_> = jpegs_with_geo
-        .iter()
-        .map(|(_, geoloc)| [geoloc.latitude(), geoloc.longitude()])
-        .collect();
-
-    let geolocs = Array2::from(hdbscan_input_vector);
-
-    let mut hdbscan = HDbscan {
-        eps: f64::MAX,
-        alpha: 1.,
-        min_samples: 2,
-        min_cluster_size: 4,
-        metric: Haversine::default(),
-        boruvka: false,
+    // Only `SpaceTime` gets the hours, as every photo left for it has a capture time. The
+    // ball trees of the other metrics would take them for a third coordinate.
+    let geolocs = match parameters.metric {
+        DistanceMetric::SpaceTime { .. } => Array2::from(
+            groups
+                .iter()
+                .map(|group| {
+                    let photo = &group.photos[0];
+                    let hours = photo
+                        .date_time
+                        .and_then(ZonedDateTime::to_unix_timestamp)
+                        .map_or(f64::NAN, |seconds| seconds as f64 / 3600.);
+                    [photo.coord.lat, photo.coord.lon, hours]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        DistanceMetric::Distance => Array2::from(
+            groups
+                .iter()
+                .map(|group| [group.photos[0].coord.lat, group.photos[0].coord.lon])
+                .collect::<Vec<_>>(),
+        ),
     };
 
-    let condensed_tree = hdbscan.compute_condensed(&geolocs);
-    let mut tree = build_tree(&jpegs_with_geo, &condensed_tree);
+    let mut tree = clustering_strategy(parameters).cluster(&groups, &geolocs);
+    add_unclustered(&mut tree, &undated);
     let tree2 = convert_tree(&mut tree);
     validate_tree(&tree2);
 
-    let serde_tree = to_serde_tree(tree2);
+    Some(to_serde_tree(tree2))
+}
+
+pub fn launch_pipeline_for_directory(
+    ctx: &ScanContext,
+    similarity: Option<&SimilarityOptions>,
+    parameters: &ClusteringParameters,
+) -> (Option<SerdeTree>, Vec<Photo>, ScanReport) {
+    ctx.progress.start();
+    let (mut jpegs_with_geo, report) = scan_directories(ctx);
+    ctx.progress.finish();
+
+    mark_duplicates(&mut jpegs_with_geo);
 
-    // println!(
-    //     "converted tree: {:#?}",
-    //     tree2 /*serde_json::to_string(&cc).unwrap()*/
-    // );
-    // println!("number of pictures: {}", jpegs_with_geo.len());
-    // println!(
-    //     "number of leaves pointed by root: {}",
-    //     get_number_of_leaves(&tree2)
-    // );
+    let tree = cluster_photos(jpegs_with_geo.clone(), similarity, parameters);
 
-    Some(serde_tree)
+    (tree, jpegs_with_geo, report)
 }
@@ -1,22 +1,64 @@
+mod auto_commit;
 mod build_tree;
+mod capture_time;
+mod clustering_strategy;
 mod convert_tree;
-mod extract_filepath_location;
+mod exif_metadata_source;
+mod find_duplicates;
+mod find_root;
+mod gpx_track;
+mod group_similar;
 mod haversine_metric;
+mod isobmff;
 mod launch_pipeline_for_directory;
+mod metadata_cache;
+mod metadata_source;
+mod perceptual_hash;
+mod presets;
+mod raw_containers;
+mod takeout_metadata_source;
+#[cfg(test)]
+mod test_dir;
+#[cfg(test)]
+mod test_tiff;
+mod thumbnails;
+mod time_zone;
 mod to_serde_tree;
+mod tree_events;
 mod types;
+mod video_metadata_source;
+mod walk_filter;
+mod watch_directory;
+mod xmp_metadata_source;
 use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Result};
+use auto_commit::auto_commit;
 use launch_pipeline_for_directory::launch_pipeline_for_directory;
+use metadata_source::MetadataSources;
 mod static_assets;
 use static_assets::*;
 mod reorganize;
+mod scan_directory;
+mod scan_progress;
+mod session;
+mod space_time_metric;
 use actix_files::NamedFile;
 use reorganize::*;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use std::{future, path::Path};
 
-use crate::types::Commit;
+use crate::gpx_track::GpxTrack;
+use crate::metadata_cache::MetadataCache;
+use crate::presets::{delete_preset, load_presets, save_preset};
+use crate::scan_directory::ScanContext;
+use crate::session::Session;
+use crate::types::{
+    AutoReorganize, ClearCache, ClusteringParameters, Commit, ComputeClusters,
+    ComputeClustersResponse, DeletePreset, SavePreset, ScanReport, SerdeTree, ThumbnailQuery,
+};
+use crate::walk_filter::WalkFilter;
+use crate::watch_directory::{watch_directory, WatchedDirectory};
 
 async fn index(req: HttpRequest) -> Result<NamedFile> {
     let path: PathBuf = req.match_info().query("absfilepath").parse().unwrap();
@@ -24,52 +66,343 @@ async fn index(req: HttpRequest) -> Result<NamedFile> {
     Ok(NamedFile::open(path)?)
 }
 
+fn to_response(
+    tree: Option<SerdeTree>,
+    report: ScanReport,
+    parameters: ClusteringParameters,
+) -> HttpResponse {
+    match tree {
+        Some(tree) => {
+            let response = ComputeClustersResponse {
+                tree,
+                report,
+                parameters,
+            };
+            let serialized_response =
+                serde_json::to_string(&response).expect("Cannot serialize response to frontend");
+
+            HttpResponse::Ok().body(serialized_response)
+        }
+        None => {
+            let msg = format!(
+                "Not enough files found. {} files seen, {} with location.",
+                report.files_seen, report.files_with_location
+            );
+            HttpResponse::InternalServerError().body(msg)
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let compute_clusters_handle = |path: String| {
-        let path = Path::new(path.as_str());
-        if !path.is_dir() {
-            return future::ready(
-                HttpResponse::InternalServerError()
-                    .body("Not an absolute path to a directory provided."),
+    let session = web::Data::new(Session::default());
+
+    let compute_clusters_handle = |json: String, session: web::Data<Session>| async move {
+        let request: ComputeClusters = match serde_json::from_str(&json) {
+            Ok(request) => request,
+            Err(e) => {
+                let msg = format!("Malformed 'ComputeClusters' command: {}", e);
+                return HttpResponse::BadRequest().body(msg);
+            }
+        };
+
+        let roots: Vec<PathBuf> = request
+            .folders
+            .iter()
+            .map(|folder| PathBuf::from(&folder.0))
+            .collect();
+        if roots.is_empty() {
+            return HttpResponse::InternalServerError().body("No directory provided.");
+        }
+        if let Some(root) = roots.iter().find(|root| !root.is_dir()) {
+            return HttpResponse::InternalServerError().body(format!(
+                "Not an absolute path to a directory provided: {:?}",
+                root
+            ));
+        }
+
+        let track = match request.geotagging.as_ref().map(GpxTrack::load).transpose() {
+            Ok(track) => track,
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        };
+
+        let concurrency = match request.scan_concurrency {
+            Some(0) => {
+                return HttpResponse::BadRequest().body("'scan_concurrency' has to be at least 1.")
+            }
+            Some(n) => n,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+
+        if let Err(msg) = request.clustering.validate() {
+            return HttpResponse::BadRequest().body(msg);
+        }
+
+        let filter = match WalkFilter::new(&roots, &request.walk) {
+            Ok(filter) => filter.excluding(session.thumbnails.dir()),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        };
+
+        // The scan runs for minutes on a large library; off the worker threads, so that
+        // `scan_progress` keeps answering.
+        let scan = web::block(move || {
+            // A new scan replaces the watch of the previous one.
+            *session.watcher.lock().unwrap() = None;
+
+            let sources = MetadataSources::default();
+            let cache = Arc::new(if request.rebuild_cache {
+                MetadataCache::rebuild(&roots)
+            } else {
+                MetadataCache::open(&roots)
+            });
+            let generation = session.start_scan(cache.clone());
+            let ctx = ScanContext {
+                roots: &roots,
+                sources: &sources,
+                cache: &cache,
+                filter: &filter,
+                track: track.as_ref(),
+                concurrency,
+                progress: &session.scan_progress,
+                perceptual_hashes: request.group_similar.is_some(),
+            };
+            let (maybe_tree, photos, report) = launch_pipeline_for_directory(
+                &ctx,
+                request.group_similar.as_ref(),
+                &request.clustering,
+            );
+            let published = session.publish(
+                generation,
+                maybe_tree.clone(),
+                report.clone(),
+                request.clustering.clone(),
             );
+            if !published {
+                return Err(String::from("Superseded by a newer scan."));
+            }
+
+            if request.watch {
+                let directory = WatchedDirectory {
+                    roots,
+                    sources,
+                    cache,
+                    filter,
+                    track,
+                    concurrency,
+                    similarity: request.group_similar.clone(),
+                    clustering: request.clustering.clone(),
+                    photos: photos
+                        .into_iter()
+                        .map(|photo| (PathBuf::from(&photo.filepath.0), photo))
+                        .collect(),
+                    report: report.clone(),
+                    generation,
+                };
+                match watch_directory(directory, session.clone().into_inner()) {
+                    Ok(watcher) => *session.watcher.lock().unwrap() = Some(watcher),
+                    Err(e) => return Err(format!("Cannot watch folder: {}", e)),
+                }
+            }
+
+            Ok((maybe_tree, report, request.clustering))
+        })
+        .await;
+
+        match scan {
+            Ok(Ok((maybe_tree, report, parameters))) => to_response(maybe_tree, report, parameters),
+            Ok(Err(msg)) => HttpResponse::InternalServerError().body(msg),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    };
+
+    let clear_cache_handle = |json: String, session: web::Data<Session>| {
+        let request: ClearCache =
+            serde_json::from_str(&json).expect("Malformed 'ClearCache' command from frontend");
+
+        let folder = Path::new(request.folder.0.as_str());
+        match session.cache_for(folder).clear(folder) {
+            Ok(_) => future::ready(HttpResponse::Ok().body("")),
+            Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
         }
+    };
 
-        let maybe_tree = launch_pipeline_for_directory(path);
-        match maybe_tree {
-            Some(tree) => {
-                let serialized_tree =
-                    serde_json::to_string(&tree).expect("Cannot serialize response to frontend");
+    let scan_progress_handle = |session: web::Data<Session>| {
+        let progress = session.scan_progress.snapshot();
+        let serialized_progress =
+            serde_json::to_string(&progress).expect("Cannot serialize response to frontend");
+        future::ready(HttpResponse::Ok().body(serialized_progress))
+    };
 
-                future::ready(HttpResponse::Ok().body(serialized_tree))
+    let scan_report_handle = |session: web::Data<Session>| {
+        let report = session.scan_report.lock().unwrap().clone();
+        match report {
+            Some(report) => {
+                let serialized_report =
+                    serde_json::to_string(&report).expect("Cannot serialize response to frontend");
+                future::ready(HttpResponse::Ok().body(serialized_report))
             }
-            None => {
-                future::ready(HttpResponse::InternalServerError().body("Not enough files found."))
+            None => future::ready(HttpResponse::NotFound().body("No scan has been run yet.")),
+        }
+    };
+
+    let tree_handle = |session: web::Data<Session>| {
+        let tree = session.tree.lock().unwrap().clone();
+        let report = session.scan_report.lock().unwrap().clone();
+        let parameters = session.clustering.lock().unwrap().clone();
+        match report {
+            Some(report) => future::ready(to_response(tree, report, parameters)),
+            None => future::ready(HttpResponse::NotFound().body("No scan has been run yet.")),
+        }
+    };
+
+    let thumbnail_handle = |req: HttpRequest,
+                            query: web::Query<ThumbnailQuery>,
+                            session: web::Data<Session>| async move {
+        let path: PathBuf = req.match_info().query("absfilepath").parse().unwrap();
+
+        let jpeg = web::block(move || {
+            let cache = session.cache_for(path.parent().unwrap_or(&path));
+            session.thumbnails.get(&path, query.size, &cache)
+        })
+        .await;
+        match jpeg {
+            Ok(Ok(jpeg)) => HttpResponse::Ok().content_type("image/jpeg").body(jpeg),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                HttpResponse::NotFound().body(e.to_string())
+            }
+            Ok(Err(e)) => HttpResponse::InternalServerError().body(e.to_string()),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    };
+
+    let presets_handle = || match load_presets() {
+        Ok(presets) => {
+            let serialized_presets =
+                serde_json::to_string(&presets).expect("Cannot serialize response to frontend");
+            future::ready(HttpResponse::Ok().body(serialized_presets))
+        }
+        Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
+    };
+
+    let save_preset_handle = |json: String| {
+        let request: SavePreset = match serde_json::from_str(&json) {
+            Ok(request) => request,
+            Err(e) => {
+                let msg = format!("Malformed 'SavePreset' command: {}", e);
+                return future::ready(HttpResponse::BadRequest().body(msg));
             }
+        };
+
+        if request.name.trim().is_empty() {
+            return future::ready(HttpResponse::BadRequest().body("A preset needs a name."));
+        }
+        if let Err(msg) = request.parameters.validate() {
+            return future::ready(HttpResponse::BadRequest().body(msg));
+        }
+
+        match save_preset(request.name.trim(), request.parameters) {
+            Ok(_) => future::ready(HttpResponse::Ok().body("")),
+            Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
         }
     };
 
-    let reorganize_handle = |json: String| {
+    let delete_preset_handle = |json: String| {
+        let request: DeletePreset = match serde_json::from_str(&json) {
+            Ok(request) => request,
+            Err(e) => {
+                let msg = format!("Malformed 'DeletePreset' command: {}", e);
+                return future::ready(HttpResponse::BadRequest().body(msg));
+            }
+        };
+
+        match delete_preset(&request.name) {
+            Ok(_) => future::ready(HttpResponse::Ok().body("")),
+            Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
+        }
+    };
+
+    let tree_events_handle = |session: web::Data<Session>| {
+        future::ready(
+            HttpResponse::Ok()
+                .content_type("text/event-stream")
+                .streaming(session.tree_events.subscribe()),
+        )
+    };
+
+    let reorganize_handle = |json: String, session: web::Data<Session>| {
         let commit: Commit =
             serde_json::from_str(&json).expect("Malformed 'Commit' command from frontend");
 
-        match reorganize(commit) {
-            Ok(_) => future::ready(HttpResponse::Ok().body("")),
+        let cache = session.cache_for(Path::new(commit.folder.0.as_str()));
+        let capture_times = session
+            .tree
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(tree_capture_times)
+            .unwrap_or_default();
+        let dryrun = commit.dryrun;
+        match reorganize(commit, &capture_times, &MetadataSources::default(), &cache) {
+            Ok(_) => {
+                if !dryrun {
+                    session.invalidate();
+                }
+                future::ready(HttpResponse::Ok().body(""))
+            }
+            Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
+        }
+    };
+
+    let reorganize_auto_handle = |json: String, session: web::Data<Session>| {
+        let request: AutoReorganize =
+            serde_json::from_str(&json).expect("Malformed 'AutoReorganize' command from frontend");
+
+        // The tree is dropped by every reorganize, as the files it lists have moved.
+        let tree = session.tree.lock().unwrap().clone();
+        let Some(tree) = tree else {
+            let msg = "No clusters of the current files; compute them first.";
+            return future::ready(HttpResponse::Conflict().body(msg));
+        };
+
+        let cache = session.cache_for(Path::new(request.folder.0.as_str()));
+        let dryrun = request.dryrun;
+        let capture_times = tree_capture_times(&tree);
+        let commit = auto_commit(&tree, request);
+        match reorganize(commit, &capture_times, &MetadataSources::default(), &cache) {
+            Ok(_) => {
+                if !dryrun {
+                    session.invalidate();
+                }
+                future::ready(HttpResponse::Ok().body(""))
+            }
             Err(e) => future::ready(HttpResponse::InternalServerError().body(e.to_string())),
         }
     };
 
     let route = move || {
         let r = get_assets().into_iter().fold(
-            App::new(),
+            App::new().app_data(session.clone()),
             |accum, (WebPath(path), FileContent(content))| {
                 let handler = move || future::ready(HttpResponse::Ok().body(content.clone()));
                 accum.route(path.as_str(), web::get().to(handler))
             },
         );
         r.route("/file/{absfilepath:.*}", web::get().to(index))
+            .route(
+                "/thumbnail/{absfilepath:.*}",
+                web::get().to(thumbnail_handle),
+            )
             .route("compute_clusters", web::post().to(compute_clusters_handle))
             .route("reorganize", web::post().to(reorganize_handle))
+            .route("reorganize_auto", web::post().to(reorganize_auto_handle))
+            .route("scan_report", web::get().to(scan_report_handle))
+            .route("scan_progress", web::get().to(scan_progress_handle))
+            .route("clear_cache", web::post().to(clear_cache_handle))
+            .route("tree", web::get().to(tree_handle))
+            .route("presets", web::get().to(presets_handle))
+            .route("save_preset", web::post().to(save_preset_handle))
+            .route("delete_preset", web::post().to(delete_preset_handle))
+            .route("tree_events", web::get().to(tree_events_handle))
     };
 
     open::that("http://127.0.0.1:3000/index.html")?;
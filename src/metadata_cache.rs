@@ -0,0 +1,286 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::find_root::find_root;
+use crate::metadata_source::MetadataSources;
+use crate::perceptual_hash::perceptual_hash;
+use crate::types::*;
+
+pub const CACHE_FILENAME: &str = ".images_organizer_cache.json";
+
+/// Bumped whenever what the sources extract changes, so that older caches are rebuilt.
+const CACHE_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile<E> {
+    version: u32,
+    entries: E,
+}
+
+/// Size and modification time of a file; a cached entry is reused only while they match.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified_nanos: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> io::Result<FileStamp> {
+        let metadata = fs::metadata(path)?;
+        let modified_nanos = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        Ok(FileStamp {
+            size: metadata.len(),
+            modified_nanos,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    stamp: FileStamp,
+    /// Stamps of the sidecars the sources read, so that adding or editing one is noticed.
+    sidecars: Vec<(PathBuf, FileStamp)>,
+    metadata: Result<PhotoMetadata, SkipReason>,
+    /// Only computed for files that end up in the tree.
+    #[serde(default)]
+    content_hash: Option<String>,
+    #[serde(default)]
+    perceptual_hash: Option<u64>,
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Metadata extracted from the files of library roots, persisted in `CACHE_FILENAME` at
+/// each root so rescans only read new or modified files.
+pub struct MetadataCache {
+    roots: Vec<PathBuf>,
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+impl MetadataCache {
+    /// Loads the caches of `roots`. A missing, unreadable or outdated cache file gives an
+    /// empty cache.
+    pub fn open(roots: &[PathBuf]) -> MetadataCache {
+        let mut entries = HashMap::new();
+        for root in roots {
+            let loaded: Option<CacheFile<HashMap<PathBuf, CacheEntry>>> =
+                fs::read_to_string(root.join(CACHE_FILENAME))
+                    .ok()
+                    .and_then(|content| serde_json::from_str(&content).ok());
+            if let Some(file) = loaded.filter(|file| file.version == CACHE_VERSION) {
+                entries.extend(file.entries);
+            }
+        }
+
+        MetadataCache {
+            roots: roots.to_vec(),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// An empty cache of `roots` that overwrites whatever is stored once saved.
+    pub fn rebuild(roots: &[PathBuf]) -> MetadataCache {
+        MetadataCache {
+            roots: roots.to_vec(),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drops the entries of the files below `root`, in memory and on disk.
+    pub fn clear(&self, root: &Path) -> io::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(root));
+        match fs::remove_file(root.join(CACHE_FILENAME)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn get_sidecar_stamps(path: &Path, sources: &MetadataSources) -> Vec<(PathBuf, FileStamp)> {
+        sources
+            .sidecars(path)
+            .into_iter()
+            .filter_map(|sidecar| {
+                let stamp = FileStamp::of(&sidecar).ok()?;
+                Some((sidecar, stamp))
+            })
+            .collect()
+    }
+
+    pub fn extract(
+        &self,
+        path: &Path,
+        sources: &MetadataSources,
+    ) -> Result<PhotoMetadata, SkipReason> {
+        let stamp = FileStamp::of(path)?;
+        let sidecars = Self::get_sidecar_stamps(path, sources);
+
+        if let Some(entry) = self.entries.lock().unwrap().get(path) {
+            // Caches written before transient failures were kept out may still hold some.
+            let transient = matches!(&entry.metadata, Err(reason) if reason.is_transient());
+            if entry.stamp == stamp && entry.sidecars == sidecars && !transient {
+                return entry.metadata.clone();
+            }
+        }
+
+        let metadata = sources.extract(path);
+        // Transient failures are retried on the next scan.
+        if !matches!(&metadata, Err(reason) if reason.is_transient()) {
+            let entry = CacheEntry {
+                stamp,
+                sidecars,
+                metadata: metadata.clone(),
+                content_hash: None,
+                perceptual_hash: None,
+            };
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), entry);
+        }
+
+        metadata
+    }
+
+    /// Returns the value of `field` of the entry of `path`, computing and storing it if
+    /// the entry does not have it yet. Failures are not stored.
+    fn get_or_compute<T: Clone>(
+        &self,
+        path: &Path,
+        field: fn(&mut CacheEntry) -> &mut Option<T>,
+        compute: impl FnOnce() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let stamp = FileStamp::of(path)?;
+
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(path) {
+            if entry.stamp == stamp {
+                if let Some(value) = field(entry) {
+                    return Ok(value.clone());
+                }
+            }
+        }
+
+        let value = compute()?;
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(path) {
+            if entry.stamp == stamp {
+                *field(entry) = Some(value.clone());
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Size of `path` as of the last `extract`.
+    pub fn file_size(&self, path: &Path) -> Option<u64> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(path).map(|entry| entry.stamp.size)
+    }
+
+    /// Hex SHA-256 of the contents of `path`.
+    pub fn content_hash(&self, path: &Path) -> io::Result<String> {
+        self.get_or_compute(path, |entry| &mut entry.content_hash, || hash_file(path))
+    }
+
+    pub fn perceptual_hash(&self, path: &Path) -> io::Result<u64> {
+        self.get_or_compute(
+            path,
+            |entry| &mut entry.perceptual_hash,
+            || perceptual_hash(path),
+        )
+    }
+
+    /// Sidecars read along with the files of the cache.
+    pub fn sidecar_paths(&self) -> HashSet<PathBuf> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .values()
+            .flat_map(|entry| entry.sidecars.iter().map(|(path, _)| path.clone()))
+            .collect()
+    }
+
+    /// Keeps the entry of a file that was moved without being modified.
+    pub fn rename(&self, from: &Path, to: &Path) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.remove(from) {
+            entries.insert(to.to_path_buf(), entry);
+        }
+    }
+
+    /// Drops entries of files that are no longer there.
+    pub fn retain(&self, existing: &HashSet<PathBuf>) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|path, _| existing.contains(path));
+    }
+
+    /// Writes each entry to the cache file of the root its file lies in.
+    pub fn save(&self) -> io::Result<()> {
+        let entries = self.entries.lock().unwrap();
+        let mut per_root: HashMap<&PathBuf, HashMap<&PathBuf, &CacheEntry>> = self
+            .roots
+            .iter()
+            .map(|root| (root, HashMap::new()))
+            .collect();
+        for (path, entry) in entries.iter() {
+            if let Some(root) = find_root(&self.roots, path) {
+                per_root.entry(root).or_default().insert(path, entry);
+            }
+        }
+
+        for (root, entries) in per_root {
+            let file = CacheFile {
+                version: CACHE_VERSION,
+                entries,
+            };
+            let serialized = serde_json::to_string(&file).map_err(io::Error::other)?;
+            fs::write(root.join(CACHE_FILENAME), serialized)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn clearing_drops_the_entries_in_memory() {
+        let dir = TestDir::new();
+        let file = dir.write("notes.txt", "not a photo");
+        let root = file.parent().unwrap().to_path_buf();
+        let roots = [root.clone()];
+
+        let cache = MetadataCache::open(&roots);
+        let _ = cache.extract(&file, &MetadataSources::default());
+        cache.save().unwrap();
+        assert!(MetadataCache::open(&roots).file_size(&file).is_some());
+
+        cache.clear(&root).unwrap();
+        assert!(!root.join(CACHE_FILENAME).exists());
+        assert!(cache.file_size(&file).is_none());
+
+        cache.save().unwrap();
+        assert!(MetadataCache::open(&roots).file_size(&file).is_none());
+    }
+}
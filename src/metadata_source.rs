@@ -0,0 +1,116 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::exif_metadata_source::ExifMetadataSource;
+use crate::takeout_metadata_source::TakeoutMetadataSource;
+use crate::types::*;
+use crate::video_metadata_source::VideoMetadataSource;
+use crate::xmp_metadata_source::XmpMetadataSource;
+
+pub const IMAGE_EXTENSIONS: [&str; 13] = [
+    "jpg", "jpeg", "heif", "heic", "tiff", "png", "cr2", "cr3", "nef", "arw", "dng", "raf", "orf",
+];
+
+pub const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mov", "m4v", "3gp"];
+
+/// The raw formats among `IMAGE_EXTENSIONS`.
+pub const RAW_EXTENSIONS: [&str; 7] = ["cr2", "cr3", "nef", "arw", "dng", "raf", "orf"];
+
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(|extension| extensions.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+pub fn is_media_file(path: &Path) -> bool {
+    has_extension(path, &IMAGE_EXTENSIONS) || has_extension(path, &VIDEO_EXTENSIONS)
+}
+
+/// Other media files named like `path` but for the extension, such as the JPEG shot
+/// together with a raw.
+pub fn media_files_sharing_stem(path: &Path) -> Vec<PathBuf> {
+    let own_extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase);
+
+    IMAGE_EXTENSIONS
+        .iter()
+        .chain(VIDEO_EXTENSIONS.iter())
+        .filter(|extension| own_extension.as_deref() != Some(**extension))
+        .flat_map(|extension| {
+            [
+                path.with_extension(extension),
+                path.with_extension(extension.to_uppercase()),
+            ]
+        })
+        .filter(|candidate| candidate.is_file())
+        .collect()
+}
+
+/// A single way of learning something about a photo file.
+///
+/// A source returns `Ok(None)` when it has nothing to say about the file (format it does
+/// not handle, missing sidecar) and an error when it should have been able to read it but
+/// failed. Fields it cannot fill stay `None`.
+pub trait MetadataSource: Send + Sync {
+    fn extract(&self, path: &Path) -> Result<Option<PhotoMetadata>, SkipReason>;
+
+    /// Files next to `path` the source reads from; they travel with the photo when it is moved.
+    fn sidecars(&self, _path: &Path) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Sources ordered by priority. For every field the first source that provides it wins.
+pub struct MetadataSources(pub Vec<Box<dyn MetadataSource>>);
+
+impl MetadataSources {
+    /// Errors of individual sources only surface when no source could read the file;
+    /// the one of the source with the highest priority is returned then.
+    pub fn extract(&self, path: &Path) -> Result<PhotoMetadata, SkipReason> {
+        let mut first_error = None;
+        let mut metadata: Option<PhotoMetadata> = None;
+        for source in self.0.iter() {
+            match source.extract(path) {
+                Ok(Some(m)) => {
+                    metadata = Some(match metadata {
+                        Some(acc) => acc.or(m),
+                        None => m,
+                    })
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        metadata.ok_or(first_error.unwrap_or(SkipReason::UnsupportedFormat))
+    }
+
+    pub fn sidecars(&self, path: &Path) -> Vec<PathBuf> {
+        let mut sidecars: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|source| source.sidecars(path))
+            .collect();
+        sidecars.sort();
+        sidecars.dedup();
+        sidecars
+    }
+}
+
+impl Default for MetadataSources {
+    fn default() -> Self {
+        MetadataSources(vec![
+            Box::new(ExifMetadataSource {}),
+            Box::new(VideoMetadataSource {}),
+            Box::new(XmpMetadataSource {}),
+            Box::new(TakeoutMetadataSource {}),
+        ])
+    }
+}
@@ -0,0 +1,21 @@
+use std::{io, path::Path};
+
+/// 64-bit difference hash: one bit per horizontally adjacent pair of pixels of a 9x8
+/// grayscale thumbnail, set when the left one is darker. Resized and recompressed copies
+/// differ in a few bits at most.
+pub fn perceptual_hash(path: &Path) -> io::Result<u64> {
+    let image = image::open(path).map_err(io::Error::other)?;
+    let thumbnail = image.thumbnail_exact(9, 8).into_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if thumbnail.get_pixel(x, y)[0] < thumbnail.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(hash)
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+use crate::types::{ClusteringParameters, DistanceMetric};
+
+fn presets_path() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("images_organizer")
+        .join("presets.json")
+}
+
+/// Always offered; a saved preset of the same name replaces one of these.
+fn builtin_presets() -> BTreeMap<String, ClusteringParameters> {
+    BTreeMap::from([
+        (String::from("trips"), ClusteringParameters::default()),
+        (
+            String::from("city"),
+            ClusteringParameters {
+                min_samples: 1,
+                min_cluster_size: 2,
+                ..ClusteringParameters::default()
+            },
+        ),
+        (
+            String::from("visits"),
+            ClusteringParameters {
+                metric: DistanceMetric::SpaceTime {
+                    meters_per_hour: 1000.,
+                },
+                ..ClusteringParameters::default()
+            },
+        ),
+    ])
+}
+
+fn load_saved() -> io::Result<BTreeMap<String, ClusteringParameters>> {
+    match fs::read_to_string(presets_path()) {
+        Ok(content) => serde_json::from_str(&content).map_err(io::Error::other),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_saved(presets: &BTreeMap<String, ClusteringParameters>) -> io::Result<()> {
+    let path = presets_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let serialized = serde_json::to_string_pretty(presets).map_err(io::Error::other)?;
+    fs::write(path, serialized)
+}
+
+/// Built-in and saved presets by name.
+pub fn load_presets() -> io::Result<BTreeMap<String, ClusteringParameters>> {
+    let mut presets = builtin_presets();
+    presets.extend(load_saved()?);
+    Ok(presets)
+}
+
+pub fn save_preset(name: &str, parameters: ClusteringParameters) -> io::Result<()> {
+    let mut saved = load_saved()?;
+    saved.insert(name.to_string(), parameters);
+    write_saved(&saved)
+}
+
+/// Deleting a built-in preset only undoes changes saved under its name.
+pub fn delete_preset(name: &str) -> io::Result<()> {
+    let mut saved = load_saved()?;
+    saved.remove(name);
+    write_saved(&saved)
+}
@@ -0,0 +1,236 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use exif::{Context, Field, In, Reader, Tag};
+
+use crate::isobmff::*;
+use crate::types::SkipReason;
+
+/// Identifies the Canon metadata box inside `moov` of a CR3 file.
+const CANON_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+const RAF_MAGIC: &[u8] = b"FUJIFILMCCD-RAW ";
+
+/// Exif, GPS and interoperability IFD pointers.
+const SUB_IFD_TAGS: [u16; 3] = [0x8769, 0x8825, 0xa005];
+
+/// A TIFF file has at most a handful of IFDs; more means a loop of offsets.
+const MAX_IFDS: usize = 16;
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = vec![0u8; length];
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Size of a single value of the given TIFF field type, 0 for unknown types.
+fn tiff_type_size(field_type: u16) -> u64 {
+    match field_type {
+        1 | 2 | 6 | 7 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+/// Length of the part of a TIFF file holding its IFDs and the values they point to, which
+/// in a RAW file ends long before the image data. IFDs past the end of the file are left
+/// for the EXIF parser to report.
+fn tiff_metadata_len(file: &mut File, file_size: u64, header: &[u8; 8]) -> io::Result<u64> {
+    let little_endian = header.starts_with(b"II");
+    let u16_at = |data: &[u8], at: usize| {
+        let bytes = [data[at], data[at + 1]];
+        if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    };
+    let u32_at = |data: &[u8], at: usize| {
+        let bytes = [data[at], data[at + 1], data[at + 2], data[at + 3]];
+        if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    };
+
+    let mut end = header.len() as u64;
+    // (offset, whether the IFD links to a next one)
+    let mut pending = vec![(u32_at(header, 4) as u64, true)];
+    let mut visited = 0;
+    while let Some((offset, chained)) = pending.pop() {
+        if offset == 0 || offset + 2 > file_size || visited == MAX_IFDS {
+            continue;
+        }
+        visited += 1;
+
+        let count = u16_at(&read_at(file, offset, 2)?, 0) as usize;
+        if offset + 2 + count as u64 * 12 + 4 > file_size {
+            end = file_size;
+            continue;
+        }
+        let entries = read_at(file, offset + 2, count * 12 + 4)?;
+        end = end.max(offset + 2 + entries.len() as u64);
+
+        for entry in entries[..count * 12].chunks_exact(12) {
+            let tag = u16_at(entry, 0);
+            let length = tiff_type_size(u16_at(entry, 2)) * u32_at(entry, 4) as u64;
+            let value = u32_at(entry, 8) as u64;
+            if length > 4 {
+                end = end.max(value + length);
+            }
+            if SUB_IFD_TAGS.contains(&tag) {
+                pending.push((value, false));
+            }
+        }
+        if chained {
+            pending.push((u32_at(&entries, count * 12) as u64, true));
+        }
+    }
+
+    Ok(end)
+}
+
+/// ORF is TIFF with its own magic number (`IIRO`, `IIRS` or `MMOR`) in place of 42. Only
+/// the IFDs are read, not the tens of megabytes of sensor data after them.
+pub fn read_orf(path: &Path) -> Result<Vec<Field>, SkipReason> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+    match &header[0..4] {
+        b"IIRO" | b"IIRS" => header[2..4].copy_from_slice(&[0x2a, 0x00]),
+        b"MMOR" => header[2..4].copy_from_slice(&[0x00, 0x2a]),
+        _ => {}
+    }
+
+    let file_size = file.metadata()?.len();
+    let length = tiff_metadata_len(&mut file, file_size, &header)?.min(file_size);
+    let mut data = read_at(&mut file, 0, length as usize)?;
+    data[..header.len()].copy_from_slice(&header);
+
+    let exif = Reader::new().read_raw(data)?;
+    Ok(exif.fields().cloned().collect())
+}
+
+/// RAF keeps the EXIF block in an embedded JPEG preview whose offset and length are
+/// stored big endian at bytes 84 and 88 of the header.
+pub fn read_raf(path: &Path) -> Result<Vec<Field>, SkipReason> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 92];
+    file.read_exact(&mut header)?;
+    if !header.starts_with(RAF_MAGIC) {
+        return Err(SkipReason::ParseError(String::from("Not a RAF file")));
+    }
+
+    let offset = u32::from_be_bytes([header[84], header[85], header[86], header[87]]) as u64;
+    let length = u32::from_be_bytes([header[88], header[89], header[90], header[91]]) as u64;
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut jpeg = Vec::new();
+    file.take(length).read_to_end(&mut jpeg)?;
+
+    let exif = Reader::new().read_from_container(&mut Cursor::new(jpeg))?;
+    Ok(exif.fields().cloned().collect())
+}
+
+/// CR3 is ISO-BMFF. The Canon box in `moov` holds separate TIFF structures for IFD0
+/// (`CMT1`), the Exif IFD (`CMT2`) and the GPS IFD (`CMT4`), each stored as IFD0 of its
+/// own TIFF, so the tags are re-attached to the IFD they really belong to.
+pub fn read_cr3(path: &Path) -> Result<Vec<Field>, SkipReason> {
+    let no_metadata = || SkipReason::ParseError(String::from("No Canon metadata box in CR3"));
+
+    let moov = read_top_level_box(path, b"moov")?.ok_or_else(no_metadata)?;
+    let canon = children(&moov)
+        .into_iter()
+        .find(|(box_type, payload)| *box_type == b"uuid" && payload.starts_with(&CANON_UUID))
+        .map(|(_, payload)| &payload[CANON_UUID.len()..])
+        .ok_or_else(no_metadata)?;
+
+    let fields: Vec<_> = [
+        (b"CMT1", Context::Tiff),
+        (b"CMT2", Context::Exif),
+        (b"CMT4", Context::Gps),
+    ]
+    .into_iter()
+    .filter_map(|(box_type, context)| {
+        let (fields, _) = exif::parse_exif(find_child(canon, box_type)?).ok()?;
+        Some(
+            fields
+                .into_iter()
+                .filter(|field| field.ifd_num == In::PRIMARY)
+                .map(move |field| Field {
+                    tag: Tag(context, field.tag.number()),
+                    ..field
+                }),
+        )
+    })
+    .flatten()
+    .collect();
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::test_tiff::*;
+
+    #[test]
+    fn reads_orf_up_to_the_sensor_data() {
+        let ifd0 = Ifd {
+            children: vec![(0x8769, vec![exif_ifd()]), (0x8825, vec![gps_ifd()])],
+            ..raw_image_ifd()
+        };
+        let tiff = Tiff {
+            big_endian: false,
+            magic: *b"RO",
+            header_extension: Vec::new(),
+            ifd0,
+        };
+        let (data, chain) = tiff.build();
+        let dir = TestDir::new();
+        let path = dir.write("P7140001.ORF", &data);
+
+        // IFD0 is written last, after the IFDs and values it points to.
+        let ifd0_end = chain[0] as u64 + 2 + 6 * 12 + 4;
+        let mut file = File::open(&path).unwrap();
+        let header: [u8; 8] = data[..8].try_into().unwrap();
+        let length = tiff_metadata_len(&mut file, data.len() as u64, &header).unwrap();
+        assert_eq!(length, ifd0_end);
+        assert!(length < SENSOR_DATA_OFFSET as u64);
+
+        let fields = read_orf(&path).unwrap();
+        assert!(fields.iter().any(|field| field.tag == Tag::GPSLatitude));
+        assert!(fields
+            .iter()
+            .any(|field| field.tag == Tag::DateTimeOriginal));
+    }
+
+    #[test]
+    fn stops_at_the_end_of_a_truncated_file() {
+        let tiff = Tiff {
+            big_endian: true,
+            magic: *b"OR",
+            header_extension: Vec::new(),
+            ifd0: raw_image_ifd(),
+        };
+        let (data, chain) = tiff.build();
+        let truncated = &data[..chain[0] as usize + 10];
+        let dir = TestDir::new();
+        let path = dir.write("P7140001.ORF", truncated);
+
+        let mut file = File::open(&path).unwrap();
+        let header: [u8; 8] = data[..8].try_into().unwrap();
+        let length = tiff_metadata_len(&mut file, truncated.len() as u64, &header).unwrap();
+        assert_eq!(length, truncated.len() as u64);
+    }
+}
@@ -1,12 +1,15 @@
 use core::panic;
-use exif::{DateTime, In, Tag};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, create_dir};
 use std::hash::{Hash, Hasher};
-use std::io::{self, Error};
-use std::path::Path;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
 
-use crate::types::{Cluster, Commit, Filepath};
+use crate::find_duplicates::find_duplicates;
+use crate::metadata_cache::MetadataCache;
+use crate::metadata_source::MetadataSources;
+use crate::types::{Cluster, Commit, Filepath, SerdeTree, ZonedDateTime};
 
 fn calculate_hash<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
@@ -14,63 +17,135 @@ fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct MyDateTime {
-    pub year: u16,
-    pub month: u8,
-    pub day: u8,
-    pub hour: u8,
-    pub minute: u8,
-    pub second: u8,
+/// The date on the clock where the photo was taken, so that an evening abroad does not
+/// land on the next day.
+fn to_string_date_time(date_time: &ZonedDateTime) -> String {
+    let local = &date_time.local;
+    format!("{:04}-{:02}-{:02}", local.year, local.month, local.day)
 }
 
-fn to_my_date_time(f: DateTime) -> MyDateTime {
-    MyDateTime {
-        year: f.year,
-        month: f.month,
-        day: f.day,
-        hour: f.hour,
-        minute: f.minute,
-        second: f.second,
+/// `IMG_1234.xmp` next to `IMG_1234.CR2` becomes `0.xmp`, any other sidecar
+/// (`IMG_1234.CR2.xmp`, `IMG_1234.jpg(1).json`) becomes `0.CR2.xmp`, `0.jpg.json`.
+fn get_sidecar_filename(path: &Path, sidecar: &Path, new_stem: &str, new_filename: &str) -> String {
+    let sidecar_ext = sidecar
+        .extension()
+        .and_then(|e| e.to_str())
+        .expect("Sidecar extension should be there");
+
+    if sidecar.file_stem() == path.file_stem() {
+        format!("{}.{}", new_stem, sidecar_ext)
+    } else {
+        format!("{}.{}", new_filename, sidecar_ext)
     }
 }
 
-fn extract_date_time(filepath: &Filepath) -> Option<MyDateTime> {
-    let path = Path::new(filepath.0.as_str());
-    let file = std::fs::File::open(path).ok()?;
-    let mut bufreader = std::io::BufReader::new(&file);
-    let exifreader = exif::Reader::new();
-    let exif = exifreader.read_from_container(&mut bufreader).ok()?;
+/// Falls back to copying when `to` is on another filesystem, e.g. when the output goes to
+/// a different scanned folder.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        result => result,
+    }
+}
 
-    let date_time_field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)?;
+/// Moves `path` into `folder` as `{new_stem}.{ext}`, taking its sidecars along. A sidecar
+/// in `moved_sidecars` already went with another file and stays there.
+fn move_with_sidecars(
+    path: &Path,
+    folder: &Path,
+    new_stem: &str,
+    dryrun: bool,
+    sources: &MetadataSources,
+    cache: &MetadataCache,
+    moved_sidecars: &mut HashSet<PathBuf>,
+) -> io::Result<()> {
+    let ext = path
+        .extension()
+        .expect("Extension should be there")
+        .to_str()
+        .expect("Cannot convert to string");
+    let new_filename = format!("{}.{}", new_stem, ext);
+    let filepath = folder.join(&new_filename);
+    let sidecars = sources.sidecars(path);
 
-    let datetime = match &date_time_field.value {
-        exif::Value::Ascii(ascii) => {
-            let f = ascii.first()?;
-            DateTime::from_ascii(f).ok()
+    if dryrun {
+        assert_directory_has_write_permission(
+            path.parent().expect("Path in cluster is not a file path"),
+        )?;
+
+        println!("Moving file from {:?} to {:?} ", path, filepath);
+    } else {
+        move_file(path, &filepath)?;
+        cache.rename(path, &filepath);
+    }
+
+    for sidecar in sidecars.iter() {
+        if !moved_sidecars.insert(sidecar.clone()) {
+            continue;
         }
-        _ => None,
-    }?;
 
-    return Some(to_my_date_time(datetime));
+        let sidecar_path =
+            folder.join(get_sidecar_filename(path, sidecar, new_stem, &new_filename));
+
+        if dryrun {
+            println!("Moving file from {:?} to {:?} ", sidecar, sidecar_path);
+        } else {
+            move_file(sidecar, &sidecar_path)?;
+        }
+    }
+
+    Ok(())
 }
 
-fn to_string_date_time(date_time: &MyDateTime) -> String {
-    format!(
-        "{:04}-{:02}-{:02}",
-        date_time.year, date_time.month, date_time.day
-    )
+/// Capture times of the photos of `tree`, as the scan worked them out.
+pub fn tree_capture_times(tree: &SerdeTree) -> HashMap<String, ZonedDateTime> {
+    fn collect(tree: &SerdeTree, capture_times: &mut HashMap<String, ZonedDateTime>) {
+        if let Some(node) = &tree.node {
+            for child in node.children.iter() {
+                collect(child, capture_times);
+            }
+        }
+        if let Some(leaf) = &tree.leaf {
+            let details = &leaf.details;
+            let capture_time = details.timestamp.and_then(|timestamp| {
+                let offset_seconds = details.utc_offset_seconds.unwrap_or(0);
+                ZonedDateTime::from_unix_timestamp(timestamp, offset_seconds)
+            });
+            if let Some(capture_time) = capture_time {
+                capture_times.insert(leaf.filepath.0.clone(), capture_time);
+            }
+        }
+    }
+
+    let mut capture_times = HashMap::new();
+    collect(tree, &mut capture_times);
+    capture_times
 }
 
-fn reorganize_single_cluster(
+/// The files of `cluster` earliest first, and the name of their folder after the first and
+/// last capture dates. Files missing from `capture_times` are read again, without the
+/// positions a GPX track gave them.
+fn order_cluster(
     cluster: &Cluster,
-    base_folder: &Path,
-    dryrun: bool,
-) -> io::Result<()> {
+    capture_times: &HashMap<String, ZonedDateTime>,
+    sources: &MetadataSources,
+    cache: &MetadataCache,
+) -> (Vec<Filepath>, String) {
     let mut with_datetime: Vec<_> = cluster
         .0
         .iter()
-        .map(|path| (path, extract_date_time(path)))
+        .map(|path| {
+            let date_time = capture_times.get(&path.0).copied().or_else(|| {
+                cache
+                    .extract(Path::new(path.0.as_str()), sources)
+                    .ok()
+                    .and_then(|metadata| metadata.capture_time(metadata.coord.as_ref()))
+            });
+            (path, date_time)
+        })
         .collect();
 
     with_datetime.sort_by(|(_, d1), (_, d2)| d1.cmp(d2));
@@ -96,35 +171,87 @@ fn reorganize_single_cluster(
         _ => panic! {"impossible"},
     };
 
-    let final_folder_name = format!("{}__{:06x}", name, calculate_hash(&cluster));
-    let folder_path = base_folder.join(&final_folder_name);
+    let files = with_datetime
+        .into_iter()
+        .map(|(file, _)| file.clone())
+        .collect();
+    (files, format!("{}__{:06x}", name, calculate_hash(&cluster)))
+}
+
+/// Moves `files`, earliest first, to `folder_path`.
+fn reorganize_single_cluster(
+    files: &[Filepath],
+    folder_path: &Path,
+    dryrun: bool,
+    sources: &MetadataSources,
+    cache: &MetadataCache,
+    groups: &HashMap<String, Vec<Filepath>>,
+    moved_sidecars: &mut HashSet<PathBuf>,
+) -> io::Result<()> {
+    let members: HashSet<&str> = files.iter().map(|file| file.0.as_str()).collect();
+    let collapsed: HashMap<&str, Vec<&Filepath>> = files
+        .iter()
+        .filter_map(|file| {
+            let rest = groups.get(&file.0)?;
+            let rest = rest
+                .iter()
+                .filter(|other| members.contains(other.0.as_str()))
+                .collect();
+            Some((file.0.as_str(), rest))
+        })
+        .collect();
+    let hidden: HashSet<&str> = collapsed
+        .values()
+        .flatten()
+        .map(|file| file.0.as_str())
+        .collect();
 
     if dryrun {
-        println!("Creating directory: {:?}", &folder_path);
+        println!("Creating directory: {:?}", folder_path);
     } else {
-        create_dir(&folder_path)?;
+        create_dir(folder_path)?;
     }
 
     let mut idx = 0;
-    for (file, _) in with_datetime.iter() {
+    for file in files {
+        if hidden.contains(file.0.as_str()) {
+            continue;
+        }
+
         let path = Path::new(file.0.as_str());
-        let ext = path
-            .extension()
-            .expect("Extension should be there")
-            .to_str()
-            .expect("Cannot convert to string")
-            .to_string();
-        let new_filename = format!("{}.{}", idx, ext);
-        let filepath = folder_path.join(&new_filename);
+        move_with_sidecars(
+            path,
+            folder_path,
+            &idx.to_string(),
+            dryrun,
+            sources,
+            cache,
+            moved_sidecars,
+        )?;
 
-        if dryrun {
-            assert_directory_has_write_permission(
-                path.parent().expect("Path in cluster is not a file path"),
-            )?;
+        if let Some(rest) = collapsed
+            .get(file.0.as_str())
+            .filter(|rest| !rest.is_empty())
+        {
+            let group_folder = folder_path.join(format!("{}_similar", idx));
+            if dryrun {
+                println!("Creating directory: {:?}", &group_folder);
+            } else {
+                create_dir(&group_folder)?;
+            }
 
-            println!("Moving file from {:?} to {:?} ", path, filepath);
-        } else {
-            fs::rename(path, filepath)?;
+            for (k, other) in rest.iter().enumerate() {
+                let other_path = Path::new(other.0.as_str());
+                move_with_sidecars(
+                    other_path,
+                    &group_folder,
+                    &k.to_string(),
+                    dryrun,
+                    sources,
+                    cache,
+                    moved_sidecars,
+                )?;
+            }
         }
 
         idx += 1;
@@ -133,6 +260,103 @@ fn reorganize_single_cluster(
     Ok(())
 }
 
+/// `misc` without repeated files. A file that is also in one of `clusters` would be moved
+/// twice, so that is an error.
+fn checked_misc(clusters: &[Cluster], mut misc: Vec<Filepath>) -> io::Result<Vec<Filepath>> {
+    let clustered: HashSet<&str> = clusters
+        .iter()
+        .flat_map(|cluster| cluster.0.iter())
+        .map(|file| file.0.as_str())
+        .collect();
+    if let Some(file) = misc.iter().find(|file| clustered.contains(file.0.as_str())) {
+        let msg = format!("{} is both in a cluster and in misc", file.0);
+        return Err(Error::new(ErrorKind::InvalidInput, msg));
+    }
+
+    let mut seen = HashSet::new();
+    misc.retain(|file| seen.insert(file.0.clone()));
+    Ok(misc)
+}
+
+/// Splits the extra copies of identical files off `clusters` and `misc`.
+fn take_duplicates(
+    clusters: &mut [Cluster],
+    misc: &mut Vec<Filepath>,
+    cache: &MetadataCache,
+) -> io::Result<Vec<Filepath>> {
+    let mut hashes = HashMap::new();
+    let files = clusters.iter().flat_map(|cluster| cluster.0.iter());
+    for file in files.chain(misc.iter()) {
+        let content_hash = cache.content_hash(Path::new(file.0.as_str()))?;
+        hashes.insert(file.0.clone(), content_hash);
+    }
+
+    let duplicates: HashSet<String> = find_duplicates(
+        hashes
+            .iter()
+            .map(|(file, hash)| (file.as_str(), hash.as_str())),
+    )
+    .into_keys()
+    .map(String::from)
+    .collect();
+
+    let mut taken = Vec::new();
+    let lists = clusters.iter_mut().map(|cluster| &mut cluster.0);
+    for files in lists.chain(std::iter::once(misc)) {
+        let (copies, kept): (Vec<Filepath>, Vec<Filepath>) = files
+            .drain(..)
+            .partition(|file| duplicates.contains(&file.0));
+        *files = kept;
+        taken.extend(copies);
+    }
+
+    Ok(taken)
+}
+
+/// Moves `files` into `folder` under their own names, numbering clashes.
+fn move_under_own_names(
+    files: &[Filepath],
+    folder: &Path,
+    dryrun: bool,
+    sources: &MetadataSources,
+    cache: &MetadataCache,
+    moved_sidecars: &mut HashSet<PathBuf>,
+) -> io::Result<()> {
+    if dryrun {
+        println!("Creating directory: {:?}", folder);
+    } else {
+        create_dir(folder)?;
+    }
+
+    let mut used = HashSet::new();
+    for file in files {
+        let path = Path::new(file.0.as_str());
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .expect("Path in cluster is not a file path");
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+
+        let mut new_stem = stem.to_string();
+        let mut n = 1;
+        while !used.insert(format!("{}.{}", new_stem, ext).to_lowercase()) {
+            new_stem = format!("{}({})", stem, n);
+            n += 1;
+        }
+        move_with_sidecars(
+            path,
+            folder,
+            &new_stem,
+            dryrun,
+            sources,
+            cache,
+            moved_sidecars,
+        )?;
+    }
+
+    Ok(())
+}
+
 fn assert_directory_has_write_permission(loc: &Path) -> io::Result<()> {
     let md = fs::metadata(loc)?;
     let permissions = md.permissions();
@@ -144,9 +368,18 @@ fn assert_directory_has_write_permission(loc: &Path) -> io::Result<()> {
     }
 }
 
-pub fn reorganize(commit: Commit) -> io::Result<()> {
+/// Moves the files of `commit`. Clusters are named after the capture times in
+/// `capture_times`, usually those of the scanned tree.
+pub fn reorganize(
+    commit: Commit,
+    capture_times: &HashMap<String, ZonedDateTime>,
+    sources: &MetadataSources,
+    cache: &MetadataCache,
+) -> io::Result<()> {
     let top_folder = Path::new(commit.folder.0.as_str());
     let output_folder = top_folder.join("categorized");
+    let mut clusters = commit.clusters.0;
+    let mut misc = checked_misc(&clusters, commit.misc)?;
 
     if commit.dryrun {
         assert_directory_has_write_permission(top_folder)?;
@@ -155,11 +388,123 @@ pub fn reorganize(commit: Commit) -> io::Result<()> {
         create_dir(&output_folder)?;
     }
 
-    commit
-        .clusters
-        .0
+    // Sidecars shared by several files go with the first one moved.
+    let mut moved_sidecars = HashSet::new();
+    if commit.move_duplicates {
+        let duplicates = take_duplicates(&mut clusters, &mut misc, cache)?;
+        if !duplicates.is_empty() {
+            let duplicates_folder = output_folder.join("duplicates");
+            move_under_own_names(
+                &duplicates,
+                &duplicates_folder,
+                commit.dryrun,
+                sources,
+                cache,
+                &mut moved_sidecars,
+            )?;
+        }
+    }
+
+    if !misc.is_empty() {
+        let misc_folder = output_folder.join("misc");
+        move_under_own_names(
+            &misc,
+            &misc_folder,
+            commit.dryrun,
+            sources,
+            cache,
+            &mut moved_sidecars,
+        )?;
+    }
+
+    let groups: HashMap<String, Vec<Filepath>> = commit
+        .collapsed_groups
         .into_iter()
+        .filter_map(|group| {
+            let (first, rest) = group.0.split_first()?;
+            Some((first.0.clone(), rest.to_vec()))
+        })
+        .collect();
+
+    let result = clusters
+        .into_iter()
+        .filter(|cluster| !cluster.0.is_empty())
         .fold(Ok(()), |accum, cluster| {
-            accum.and_then(|_| reorganize_single_cluster(&cluster, &output_folder, commit.dryrun))
+            accum.and_then(|_| {
+                let (files, folder_name) = order_cluster(&cluster, capture_times, sources, cache);
+                reorganize_single_cluster(
+                    &files,
+                    &output_folder.join(folder_name),
+                    commit.dryrun,
+                    sources,
+                    cache,
+                    &groups,
+                    &mut moved_sidecars,
+                )
+            })
+        });
+
+    if !commit.dryrun {
+        cache.save()?;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ConvexHull2, Coord, LeafDetails, NumberOfLeaves, SerdeLeaf, SerdeNode};
+
+    fn leaf(filepath: &str, timestamp: i64, utc_offset_seconds: i64) -> Box<SerdeTree> {
+        let leaf = SerdeLeaf {
+            filepath: Filepath(filepath.to_string()),
+            coord: Coord { lat: 0., lon: 0. },
+            details: LeafDetails {
+                timestamp: Some(timestamp),
+                utc_offset_seconds: Some(utc_offset_seconds),
+                ..LeafDetails::default()
+            },
+        };
+        Box::new(SerdeTree {
+            node: None,
+            leaf: Some(leaf),
         })
+    }
+
+    #[test]
+    fn names_clusters_after_the_capture_times_of_the_tree() {
+        // Neither file exists, so their dates can only come from the tree: 2023-07-15
+        // 01:30 and 2023-07-14 10:00, both at +02:00.
+        let tree = SerdeTree {
+            node: Some(SerdeNode {
+                convex_hull: ConvexHull2(Default::default()),
+                number_of_leaves: NumberOfLeaves(2),
+                children: [
+                    leaf("/photos/late.jpg", 1_689_377_400, 7200),
+                    leaf("/photos/early.jpg", 1_689_321_600, 7200),
+                ]
+                .into_iter()
+                .collect(),
+                details: Default::default(),
+            }),
+            leaf: None,
+        };
+        let cluster = Cluster(vec![
+            Filepath(String::from("/photos/late.jpg")),
+            Filepath(String::from("/photos/early.jpg")),
+        ]);
+
+        let cache = MetadataCache::rebuild(&[]);
+        let (files, name) = order_cluster(
+            &cluster,
+            &tree_capture_times(&tree),
+            &MetadataSources::default(),
+            &cache,
+        );
+
+        let files: Vec<&str> = files.iter().map(|file| file.0.as_str()).collect();
+        assert_eq!(files, ["/photos/early.jpg", "/photos/late.jpg"]);
+        assert!(name.starts_with("2023-07-14_2023-07-15__"), "{name}");
+    }
 }
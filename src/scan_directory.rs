@@ -0,0 +1,198 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use walkdir::WalkDir;
+
+use crate::find_root::find_root;
+use crate::gpx_track::GpxTrack;
+use crate::metadata_cache::{MetadataCache, CACHE_FILENAME};
+use crate::metadata_source::*;
+use crate::scan_progress::ScanProgress;
+use crate::types::*;
+use crate::walk_filter::WalkFilter;
+
+/// Everything a scan needs.
+pub struct ScanContext<'a> {
+    /// Folders scanned together; files found in more than one are read once.
+    pub roots: &'a [PathBuf],
+    pub sources: &'a MetadataSources,
+    pub cache: &'a MetadataCache,
+    pub filter: &'a WalkFilter,
+    pub track: Option<&'a GpxTrack>,
+    /// Number of files read at the same time; keeps slow disks from being thrashed.
+    pub concurrency: usize,
+    pub progress: &'a ScanProgress,
+    /// Decodes every photo to compare how they look; see `group_similar`.
+    pub perceptual_hashes: bool,
+}
+
+fn to_filepath(path: &Path) -> Result<Filepath, SkipReason> {
+    path.to_str()
+        .map(|p| Filepath(String::from(p)))
+        .ok_or_else(|| SkipReason::ParseError(String::from("Path is not valid UTF-8")))
+}
+
+fn locate(
+    metadata: &PhotoMetadata,
+    track: Option<&GpxTrack>,
+) -> Result<(Coord, LeafDetails), SkipReason> {
+    if let Some(coord) = metadata.coord {
+        return Ok((coord, LeafDetails::default()));
+    }
+
+    let interpolated = track.and_then(|t| t.locate(metadata));
+    match (interpolated, &metadata.gps_error) {
+        (Some(coord), _) => Ok((
+            coord,
+            LeafDetails {
+                interpolated: true,
+                ..LeafDetails::default()
+            },
+        )),
+        (None, Some(e)) => Err(SkipReason::InvalidGps(e.to_string())),
+        (None, None) => Err(SkipReason::NoGps),
+    }
+}
+
+fn scan_file(path: &Path, ctx: &ScanContext) -> Result<Photo, SkipReason> {
+    let filepath = to_filepath(path)?;
+    let metadata = ctx.cache.extract(path, ctx.sources)?;
+    let (coord, mut details) = locate(&metadata, ctx.track)?;
+    let date_time = metadata.capture_time(Some(&coord));
+    if let Some(root) = find_root(ctx.roots, path) {
+        details.root = Folder(root.to_string_lossy().to_string());
+    }
+    details.timestamp = date_time.and_then(ZonedDateTime::to_unix_timestamp);
+    details.utc_offset_seconds = date_time.map(|d| d.offset_seconds);
+    details.file_size = ctx.cache.file_size(path).unwrap_or_default();
+    details.properties = metadata.properties;
+    let content_hash = ctx.cache.content_hash(path)?;
+    let perceptual_hash = if ctx.perceptual_hashes {
+        ctx.cache.perceptual_hash(path).ok()
+    } else {
+        None
+    };
+
+    Ok(Photo {
+        filepath,
+        coord,
+        details,
+        content_hash,
+        perceptual_hash,
+        date_time,
+    })
+}
+
+/// Lists the files below `path` that a scan reads, recording the entries that cannot be
+/// walked in `report`.
+pub fn walk_directory(path: &Path, ctx: &ScanContext, report: &mut ScanReport) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    let walker = WalkDir::new(path)
+        .follow_links(ctx.filter.follow_symlinks)
+        .into_iter()
+        .filter_entry(|entry| ctx.filter.accepts(entry.path(), entry.file_type().is_dir()));
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let filepath = Filepath(
+                    e.path()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                );
+                let reason = match e.into_io_error() {
+                    Some(e) => SkipReason::from(e),
+                    None => SkipReason::IoError(String::from("Filesystem loop")),
+                };
+                report.skip(filepath, reason);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() || entry.file_name() == CACHE_FILENAME {
+            continue;
+        }
+
+        ctx.progress.file_seen();
+        files.push(entry.into_path());
+    }
+
+    files
+}
+
+/// Reads `files` on `ctx.concurrency` worker threads. Results come back in the order of
+/// `files`.
+pub fn scan_files(files: &[PathBuf], ctx: &ScanContext) -> Vec<Result<Photo, SkipReason>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(files.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..ctx.concurrency.max(1) {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(idx) else {
+                    break;
+                };
+
+                let result = scan_file(file, ctx);
+                ctx.progress.file_parsed(result.is_ok());
+                results.lock().unwrap().push((idx, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Reads every file below `ctx.roots`. Photos come back in the order the directory walks
+/// found them.
+pub fn scan_directories(ctx: &ScanContext) -> (Vec<Photo>, ScanReport) {
+    let mut report = ScanReport::default();
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for root in ctx.roots {
+        for file in walk_directory(root, ctx, &mut report) {
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    report.files_seen = files.len();
+
+    let results = scan_files(&files, ctx);
+
+    ctx.cache.retain(&seen);
+    if let Err(e) = ctx.cache.save() {
+        eprintln!("Cannot save metadata cache: {}", e);
+    }
+
+    let sidecars = ctx.cache.sidecar_paths();
+    let mut photos = Vec::new();
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Ok(photo) => {
+                report.files_with_location += 1;
+                photos.push(photo);
+            }
+            Err(_) if sidecars.contains(file) => {
+                report.add_sidecar(Filepath(file.to_string_lossy().to_string()));
+            }
+            Err(reason) => {
+                let filepath = Filepath(file.to_string_lossy().to_string());
+                report.skip(filepath, reason);
+            }
+        }
+    }
+
+    (photos, report)
+}
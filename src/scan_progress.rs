@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// Counters updated by the scan workers while a scan runs.
+#[derive(Default)]
+pub struct ScanProgress {
+    running: AtomicBool,
+    files_seen: AtomicUsize,
+    files_parsed: AtomicUsize,
+    files_with_gps: AtomicUsize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Progress {
+    pub running: bool,
+    pub files_seen: usize,
+    pub files_parsed: usize,
+    pub files_with_gps: usize,
+}
+
+impl ScanProgress {
+    pub fn start(&self) {
+        self.files_seen.store(0, Ordering::Relaxed);
+        self.files_parsed.store(0, Ordering::Relaxed);
+        self.files_with_gps.store(0, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub fn file_seen(&self) {
+        self.files_seen.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn file_parsed(&self, with_gps: bool) {
+        self.files_parsed.fetch_add(1, Ordering::Relaxed);
+        if with_gps {
+            self.files_with_gps.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Progress {
+        Progress {
+            running: self.running.load(Ordering::Relaxed),
+            files_seen: self.files_seen.load(Ordering::Relaxed),
+            files_parsed: self.files_parsed.load(Ordering::Relaxed),
+            files_with_gps: self.files_with_gps.load(Ordering::Relaxed),
+        }
+    }
+}
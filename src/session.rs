@@ -0,0 +1,80 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::metadata_cache::MetadataCache;
+use crate::scan_progress::ScanProgress;
+use crate::thumbnails::Thumbnails;
+use crate::tree_events::TreeEvents;
+use crate::types::*;
+use crate::watch_directory::DirectoryWatcher;
+
+/// State shared by all request handlers of the running server.
+#[derive(Default)]
+pub struct Session {
+    pub scan_report: Mutex<Option<ScanReport>>,
+    pub scan_progress: ScanProgress,
+    /// The tree of the last scan, kept up to date while the folder is watched.
+    pub tree: Mutex<Option<SerdeTree>>,
+    /// The parameters `tree` was clustered with.
+    pub clustering: Mutex<ClusteringParameters>,
+    /// The metadata cache of the scanned folders, shared by the watch and `reorganize` so
+    /// that neither overwrites the entries of the other.
+    pub cache: Mutex<Option<Arc<MetadataCache>>>,
+    /// Bumped by every scan. Results of an older scan, or of the watch of one, are dropped.
+    generation: Mutex<u64>,
+    pub watcher: Mutex<Option<DirectoryWatcher>>,
+    pub tree_events: TreeEvents,
+    pub thumbnails: Thumbnails,
+}
+
+impl Session {
+    /// Starts a new generation reading and filling `cache`, so that whatever earlier scans
+    /// are still computing is not published anymore.
+    pub fn start_scan(&self, cache: Arc<MetadataCache>) -> u64 {
+        let mut generation = self.generation.lock().unwrap();
+        *generation += 1;
+        *self.cache.lock().unwrap() = Some(cache);
+        *generation
+    }
+
+    /// The cache of the last scan, or the one stored in `folder` when nothing was scanned.
+    pub fn cache_for(&self, folder: &Path) -> Arc<MetadataCache> {
+        let cache = self.cache.lock().unwrap().clone();
+        cache.unwrap_or_else(|| Arc::new(MetadataCache::open(&[PathBuf::from(folder)])))
+    }
+
+    /// Publishes the outcome of a scan of `generation` unless a newer scan has started.
+    /// Returns whether it was published.
+    pub fn publish(
+        &self,
+        generation: u64,
+        tree: Option<SerdeTree>,
+        report: ScanReport,
+        clustering: ClusteringParameters,
+    ) -> bool {
+        let current = self.generation.lock().unwrap();
+        if *current != generation {
+            return false;
+        }
+
+        *self.tree.lock().unwrap() = tree;
+        *self.scan_report.lock().unwrap() = Some(report);
+        *self.clustering.lock().unwrap() = clustering;
+        true
+    }
+
+    /// Forgets the tree once its files were moved, so that nothing is reorganized from it
+    /// again, and stops watching them. A new scan starts over.
+    pub fn invalidate(&self) {
+        {
+            let mut generation = self.generation.lock().unwrap();
+            *generation += 1;
+            *self.tree.lock().unwrap() = None;
+        }
+        // Dropped outside the lock, which the watch may be waiting for to publish.
+        let watcher = self.watcher.lock().unwrap().take();
+        drop(watcher);
+    }
+}
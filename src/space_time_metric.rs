@@ -0,0 +1,34 @@
+use ndarray::ArrayView1;
+use petal_neighbors::distance::Metric;
+
+use crate::types::{Haversine, SpaceTime};
+
+impl SpaceTime {
+    /// Time apart scaled to meters. Photos without capture time are never clustered by
+    /// `SpaceTime`, so both times are known.
+    fn time_meters(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        (x1[2] - x2[2]).abs() * self.meters_per_hour
+    }
+}
+
+impl Metric<f64> for SpaceTime {
+    /// Haversine distance and time apart combined as the legs of a right triangle.
+    fn distance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        self.rdistance(x1, x2).sqrt()
+    }
+
+    /// Squared distance.
+    fn rdistance(&self, x1: &ArrayView1<f64>, x2: &ArrayView1<f64>) -> f64 {
+        let meters = Haversine::default().distance(x1, x2);
+        let time_meters = self.time_meters(x1, x2);
+        meters * meters + time_meters * time_meters
+    }
+
+    fn rdistance_to_distance(&self, d: f64) -> f64 {
+        d.sqrt()
+    }
+
+    fn distance_to_rdistance(&self, d: f64) -> f64 {
+        d * d
+    }
+}
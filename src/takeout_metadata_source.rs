@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::metadata_source::*;
+use crate::types::*;
+
+/// Takeout limits the sidecar name without the trailing `.json` to this many characters.
+const MAX_SIDECAR_STEM_LENGTH: usize = 46;
+
+fn truncate(name: &str) -> String {
+    name.chars().take(MAX_SIDECAR_STEM_LENGTH).collect()
+}
+
+/// Splits `IMG_1234(1).jpg` into `IMG_1234.jpg` and `(1)`; Takeout puts the duplicate
+/// counter after the extension of the sidecar: `IMG_1234.jpg(1).json`.
+fn split_duplicate_counter(file_name: &str) -> Option<(String, &str)> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let counter_start = stem.rfind('(')?;
+    let counter = &stem[counter_start..];
+    let is_counter = counter.len() > 2
+        && counter.ends_with(')')
+        && counter[1..counter.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_digit());
+    if !is_counter {
+        return None;
+    }
+
+    Some((format!("{}.{}", &stem[..counter_start], extension), counter))
+}
+
+/// Names Takeout gives the sidecar of `file_name`.
+fn sidecar_names(file_name: &str) -> Vec<String> {
+    let mut names = vec![
+        format!("{}.json", truncate(file_name)),
+        format!(
+            "{}.json",
+            truncate(&format!("{}.supplemental-metadata", file_name))
+        ),
+    ];
+
+    if let Some((original, counter)) = split_duplicate_counter(file_name) {
+        names.push(format!("{}{}.json", truncate(&original), counter));
+        names.push(format!(
+            "{}{}.json",
+            truncate(&format!("{}.supplemental-metadata", original)),
+            counter
+        ));
+    }
+
+    names
+}
+
+/// The sidecar written for `path` itself. `IMG_1234.json` is only taken when no other
+/// media file is named `IMG_1234`.
+fn find_own_sidecar(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+
+    let own = sidecar_names(file_name)
+        .into_iter()
+        .map(|name| path.with_file_name(name))
+        .find(|candidate| candidate.is_file());
+
+    // Looking for files sharing the stem takes a dozen lookups, so only when needed.
+    own.or_else(|| {
+        let (stem, _) = file_name.rsplit_once('.')?;
+        let candidate = path.with_file_name(format!("{}.json", truncate(stem)));
+        (candidate.is_file() && media_files_sharing_stem(path).is_empty()).then_some(candidate)
+    })
+}
+
+/// `IMG_1234-edited.jpg` gets no sidecar of its own; it shares the one of `IMG_1234.jpg`.
+fn find_original_sidecar(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let original = format!("{}.{}", stem.strip_suffix("-edited")?, extension);
+
+    sidecar_names(&original)
+        .into_iter()
+        .map(|name| path.with_file_name(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn find_sidecar(path: &Path) -> Option<PathBuf> {
+    find_own_sidecar(path).or_else(|| find_original_sidecar(path))
+}
+
+/// Takeout writes `0.0, 0.0` when the location is unknown.
+fn extract_coord(geo_data: &Value) -> Option<Coord> {
+    let lat = geo_data.get("latitude")?.as_f64()?;
+    let lon = geo_data.get("longitude")?.as_f64()?;
+    if lat == 0. && lon == 0. {
+        return None;
+    }
+
+    Some(Coord { lat, lon })
+}
+
+fn extract_utc_date_time(json: &Value) -> Option<MyDateTime> {
+    let timestamp = json.get("photoTakenTime")?.get("timestamp")?;
+    let seconds = match timestamp {
+        Value::String(s) => s.parse().ok()?,
+        other => other.as_i64()?,
+    };
+
+    MyDateTime::from_unix_timestamp(seconds)
+}
+
+/// Reads GPS position and capture time from the `.json` sidecars of a Google Takeout export.
+/// Takeout writes them for photos and videos alike.
+pub struct TakeoutMetadataSource {}
+
+impl MetadataSource for TakeoutMetadataSource {
+    fn extract(&self, path: &Path) -> Result<Option<PhotoMetadata>, SkipReason> {
+        if !is_media_file(path) {
+            return Ok(None);
+        }
+
+        let Some(sidecar) = find_sidecar(path) else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(sidecar)?;
+        let json: Value = serde_json::from_str(&content)
+            .map_err(|e| SkipReason::ParseError(format!("Takeout sidecar: {}", e)))?;
+
+        let geo_data = ["geoData", "geoDataExif"]
+            .into_iter()
+            .filter_map(|key| json.get(key))
+            .find(|geo_data| extract_coord(geo_data).is_some());
+        let properties = PhotoProperties {
+            altitude: geo_data.and_then(|geo_data| geo_data.get("altitude")?.as_f64()),
+            ..Default::default()
+        };
+
+        Ok(Some(PhotoMetadata {
+            coord: geo_data.and_then(extract_coord),
+            properties,
+            utc_date_time: extract_utc_date_time(&json),
+            ..Default::default()
+        }))
+    }
+
+    /// The sidecar of an edited copy stays with the original.
+    fn sidecars(&self, path: &Path) -> Vec<PathBuf> {
+        if !is_media_file(path) {
+            return Vec::new();
+        }
+
+        find_own_sidecar(path).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn names_the_sidecar_after_the_whole_file() {
+        let names = sidecar_names("IMG_1234.jpg");
+        assert!(names.contains(&String::from("IMG_1234.jpg.json")));
+        assert!(names.contains(&String::from("IMG_1234.jpg.supplemental-metadata.json")));
+    }
+
+    #[test]
+    fn truncates_long_names() {
+        let names = sidecar_names("PXL_20230714_183005123.NIGHT.RAW-01.MP.COVER.jpg");
+        assert!(names.contains(&String::from(
+            "PXL_20230714_183005123.NIGHT.RAW-01.MP.COVER.j.json"
+        )));
+
+        let names = sidecar_names("PXL_20230714_183005123.jpg");
+        assert!(names.contains(&String::from(
+            "PXL_20230714_183005123.jpg.supplemental-metada.json"
+        )));
+    }
+
+    #[test]
+    fn moves_the_duplicate_counter_behind_the_extension() {
+        let names = sidecar_names("IMG_1234(1).jpg");
+        assert!(names.contains(&String::from("IMG_1234.jpg(1).json")));
+        assert!(names.contains(&String::from("IMG_1234.jpg.supplemental-metadata(1).json")));
+        assert_eq!(sidecar_names("IMG_1234(a).jpg").len(), 2);
+    }
+
+    #[test]
+    fn reads_the_sidecar_of_the_original_for_an_edited_copy() {
+        let dir = TestDir::new();
+        let original = dir.write("IMG_1234.jpg", "");
+        let edited = dir.write("IMG_1234-edited.jpg", "");
+        let sidecar = dir.write("IMG_1234.jpg.json", "{}");
+
+        assert_eq!(find_sidecar(&edited), Some(sidecar.clone()));
+
+        let source = TakeoutMetadataSource {};
+        assert_eq!(source.sidecars(&original), vec![sidecar]);
+        assert!(source.sidecars(&edited).is_empty());
+    }
+
+    #[test]
+    fn takes_the_stem_sidecar_only_when_the_stem_is_not_shared() {
+        let dir = TestDir::new();
+        let photo = dir.write("IMG_1234.heic", "");
+        let sidecar = dir.write("IMG_1234.json", "{}");
+        assert_eq!(find_sidecar(&photo), Some(sidecar));
+
+        let video = dir.write("IMG_1234.mp4", "");
+        assert_eq!(find_sidecar(&photo), None);
+        assert_eq!(find_sidecar(&video), None);
+    }
+}
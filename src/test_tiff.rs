@@ -0,0 +1,217 @@
+//! Minimal TIFF-structured files, laid out the way cameras write their raws, for tests.
+
+const BYTE: u16 = 1;
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// Where the sensor data is written, well past the IFDs.
+pub const SENSOR_DATA_OFFSET: u32 = 0x1000;
+const SENSOR_DATA_LENGTH: u32 = 0x800;
+
+pub enum TiffValue {
+    Ascii(&'static str),
+    Byte(Vec<u8>),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+}
+
+#[derive(Default)]
+pub struct Ifd {
+    pub entries: Vec<(u16, TiffValue)>,
+    /// IFDs pointed to by a tag, such as the Exif and GPS IFDs or the `SubIFDs` of NEF,
+    /// ARW and DNG.
+    pub children: Vec<(u16, Vec<Ifd>)>,
+    pub next: Option<Box<Ifd>>,
+}
+
+/// 52°13'5" N, 21°0'30" E.
+pub fn gps_ifd() -> Ifd {
+    Ifd {
+        entries: vec![
+            (0x0001, TiffValue::Ascii("N")),
+            (0x0002, TiffValue::Rational(vec![(52, 1), (13, 1), (5, 1)])),
+            (0x0003, TiffValue::Ascii("E")),
+            (0x0004, TiffValue::Rational(vec![(21, 1), (0, 1), (30, 1)])),
+        ],
+        ..Ifd::default()
+    }
+}
+
+/// Taken at 2023-07-14 18:30:05.
+pub fn exif_ifd() -> Ifd {
+    Ifd {
+        entries: vec![(0x9003, TiffValue::Ascii("2023:07:14 18:30:05"))],
+        ..Ifd::default()
+    }
+}
+
+/// An IFD describing sensor data at `SENSOR_DATA_OFFSET`.
+pub fn raw_image_ifd() -> Ifd {
+    Ifd {
+        entries: vec![
+            (0x0100, TiffValue::Long(vec![6000])),
+            (0x0101, TiffValue::Long(vec![4000])),
+            (0x0111, TiffValue::Long(vec![SENSOR_DATA_OFFSET])),
+            (0x0117, TiffValue::Long(vec![SENSOR_DATA_LENGTH])),
+        ],
+        ..Ifd::default()
+    }
+}
+
+pub struct Tiff {
+    pub big_endian: bool,
+    /// Bytes 2 and 3 of the header, 42 in plain TIFF.
+    pub magic: [u8; 2],
+    /// Written after the 8 bytes of the TIFF header, e.g. the CR2 signature. IFD0 follows.
+    pub header_extension: Vec<u8>,
+    pub ifd0: Ifd,
+}
+
+impl Tiff {
+    /// The file, with sensor data after the IFDs. Also returns the offsets of IFD0 and the
+    /// IFDs chained after it.
+    pub fn build(&self) -> (Vec<u8>, Vec<u32>) {
+        let mut writer = Writer {
+            big_endian: self.big_endian,
+            data: Vec::new(),
+            chain: Vec::new(),
+        };
+        writer
+            .data
+            .extend(if self.big_endian { b"MM" } else { b"II" });
+        writer.data.extend(self.magic);
+        writer.data.extend([0; 4]);
+        writer.data.extend(&self.header_extension);
+
+        let ifd0_offset = writer.write_ifd(&self.ifd0, true);
+        let ifd0_offset = writer.u32(ifd0_offset);
+        writer.data[4..8].copy_from_slice(&ifd0_offset);
+
+        assert!(writer.data.len() <= SENSOR_DATA_OFFSET as usize);
+        writer.data.resize(SENSOR_DATA_OFFSET as usize, 0);
+        writer.data.extend(vec![0xff; SENSOR_DATA_LENGTH as usize]);
+        writer.chain.reverse();
+        (writer.data, writer.chain)
+    }
+}
+
+struct Writer {
+    big_endian: bool,
+    data: Vec<u8>,
+    chain: Vec<u32>,
+}
+
+impl Writer {
+    fn u16(&self, value: u16) -> [u8; 2] {
+        if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    fn u32(&self, value: u32) -> [u8; 4] {
+        if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    fn encode(&self, value: &TiffValue) -> (u16, u32, Vec<u8>) {
+        match value {
+            TiffValue::Ascii(s) => {
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.push(0);
+                (ASCII, bytes.len() as u32, bytes)
+            }
+            TiffValue::Byte(v) => (BYTE, v.len() as u32, v.clone()),
+            TiffValue::Short(v) => (
+                SHORT,
+                v.len() as u32,
+                v.iter().flat_map(|x| self.u16(*x)).collect(),
+            ),
+            TiffValue::Long(v) => (
+                LONG,
+                v.len() as u32,
+                v.iter().flat_map(|x| self.u32(*x)).collect(),
+            ),
+            TiffValue::Rational(v) => (
+                RATIONAL,
+                v.len() as u32,
+                v.iter()
+                    .flat_map(|(num, denom)| [self.u32(*num), self.u32(*denom)])
+                    .flatten()
+                    .collect(),
+            ),
+        }
+    }
+
+    fn align(&mut self) {
+        if self.data.len() % 2 == 1 {
+            self.data.push(0);
+        }
+    }
+
+    /// Writes the IFDs `ifd` points to, then its out-of-line values, then `ifd` itself,
+    /// and returns its offset.
+    fn write_ifd(&mut self, ifd: &Ifd, chained: bool) -> u32 {
+        let next = match &ifd.next {
+            Some(next) => self.write_ifd(next, chained),
+            None => 0,
+        };
+
+        let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = Vec::new();
+        for (tag, children) in ifd.children.iter() {
+            let offsets = children
+                .iter()
+                .map(|child| self.write_ifd(child, false))
+                .collect();
+            let (field_type, count, bytes) = self.encode(&TiffValue::Long(offsets));
+            entries.push((*tag, field_type, count, bytes));
+        }
+        for (tag, value) in ifd.entries.iter() {
+            let (field_type, count, bytes) = self.encode(value);
+            entries.push((*tag, field_type, count, bytes));
+        }
+        entries.sort_by_key(|(tag, ..)| *tag);
+
+        let mut fields = Vec::new();
+        for (_, _, _, bytes) in entries.iter() {
+            if bytes.len() > 4 {
+                self.align();
+                let offset = self.data.len() as u32;
+                self.data.extend(bytes);
+                fields.push(self.u32(offset).to_vec());
+            } else {
+                let mut inline = bytes.clone();
+                inline.resize(4, 0);
+                fields.push(inline);
+            }
+        }
+
+        self.align();
+        let offset = self.data.len() as u32;
+        let count = self.u16(entries.len() as u16);
+        self.data.extend(count);
+        for ((tag, field_type, count, _), field) in entries.iter().zip(fields) {
+            let bytes = [
+                self.u16(*tag).to_vec(),
+                self.u16(*field_type).to_vec(),
+                self.u32(*count).to_vec(),
+                field,
+            ];
+            self.data.extend(bytes.concat());
+        }
+        let next = self.u32(next);
+        self.data.extend(next);
+
+        if chained {
+            self.chain.push(offset);
+        }
+        offset
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Cursor, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use exif::{Exif, In, Reader, Tag};
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+
+use crate::metadata_cache::MetadataCache;
+
+const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 32;
+/// Large enough for full-screen previews.
+const MAX_SIZE: u32 = 2048;
+const JPEG_QUALITY: u8 = 85;
+/// Part of the file names, bumped when rendering changes so that stale thumbnails are not
+/// served.
+const RENDER_VERSION: u32 = 3;
+
+fn default_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("images_organizer")
+        .join("thumbnails")
+}
+
+fn read_exif(path: &Path) -> Option<Exif> {
+    Reader::new()
+        .read_from_container(&mut BufReader::new(File::open(path).ok()?))
+        .ok()
+}
+
+/// The preview most cameras embed after the main EXIF directory, typically 160x120. It is
+/// stored the way the sensor saw it, like the main image.
+fn read_embedded_thumbnail(exif: &Exif) -> Option<DynamicImage> {
+    let get_uint = |tag| exif.get_field(tag, In::THUMBNAIL)?.value.get_uint(0);
+    let offset = get_uint(Tag::JPEGInterchangeFormat)? as usize;
+    let length = get_uint(Tag::JPEGInterchangeFormatLength)? as usize;
+    let jpeg = exif.buf().get(offset..offset + length)?;
+
+    image::load_from_memory(jpeg).ok()
+}
+
+/// Turns the image upright according to the EXIF orientation, 1 to 8.
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Uses the embedded thumbnail when no size was asked for or it is big enough, and when the
+/// file itself cannot be decoded as is the case for raws.
+fn render(path: &Path, size: u32, any_size: bool) -> io::Result<Vec<u8>> {
+    let exif = read_exif(path);
+    let embedded = exif.as_ref().and_then(read_embedded_thumbnail);
+    let image = match embedded {
+        Some(embedded) if any_size || embedded.width().max(embedded.height()) >= size => embedded,
+        embedded => match image::open(path) {
+            Ok(image) => image,
+            Err(e) => embedded.ok_or_else(|| io::Error::other(e))?,
+        },
+    };
+
+    let orientation = exif.as_ref().and_then(|exif| {
+        exif.get_field(Tag::Orientation, In::PRIMARY)?
+            .value
+            .get_uint(0)
+    });
+    let image = apply_orientation(image, orientation.unwrap_or(1));
+
+    let (width, height) = image.dimensions();
+    let resized = if width.max(height) > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(resized.to_rgb8())
+        .write_to(
+            &mut Cursor::new(&mut jpeg),
+            ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        )
+        .map_err(io::Error::other)?;
+    Ok(jpeg)
+}
+
+/// Downscaled JPEG previews, stored on disk under the content hash of the file so that
+/// moved or renamed files keep theirs.
+pub struct Thumbnails {
+    dir: PathBuf,
+}
+
+impl Default for Thumbnails {
+    fn default() -> Self {
+        Thumbnails { dir: default_dir() }
+    }
+}
+
+impl Thumbnails {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// JPEG of `path` no larger than `size` pixels on either edge, rendered once per
+    /// content and size. Without a size the embedded thumbnail is served if there is one.
+    pub fn get(
+        &self,
+        path: &Path,
+        size: Option<u32>,
+        cache: &MetadataCache,
+    ) -> io::Result<Vec<u8>> {
+        let any_size = size.is_none();
+        let size = size.unwrap_or(DEFAULT_SIZE).clamp(MIN_SIZE, MAX_SIZE);
+        let size_name = if any_size {
+            String::from("default")
+        } else {
+            size.to_string()
+        };
+        let cached = self.dir.join(format!(
+            "{}_{}_v{}.jpg",
+            cache.content_hash(path)?,
+            size_name,
+            RENDER_VERSION
+        ));
+        match fs::read(&cached) {
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            result => return result,
+        }
+
+        let jpeg = render(path, size, any_size)?;
+        // Written aside and renamed, so a concurrent request never reads half a file.
+        let partial = cached.with_extension(format!("{:?}.partial", std::thread::current().id()));
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&partial, &jpeg)?;
+        fs::rename(&partial, &cached)?;
+        Ok(jpeg)
+    }
+}
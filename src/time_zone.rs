@@ -0,0 +1,110 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use tzf_rs::DefaultFinder;
+
+use crate::types::{Coord, MyDateTime};
+
+/// The zone boundaries bundled into the binary take a moment to load, so that is done once.
+fn finder() -> &'static DefaultFinder {
+    static FINDER: OnceLock<DefaultFinder> = OnceLock::new();
+    FINDER.get_or_init(DefaultFinder::new)
+}
+
+/// The civil time zone at `coord`, none out at sea.
+fn time_zone_at(coord: &Coord) -> Option<Tz> {
+    finder().get_tz_name(coord.lon, coord.lat).parse().ok()
+}
+
+/// Offset of the nautical time zone at `coord`, one hour per 15 degrees of longitude, which
+/// is what ships keep outside territorial waters.
+fn nautical_offset_seconds(coord: &Coord) -> i64 {
+    (coord.lon / 15.).round() as i64 * 3600
+}
+
+/// Offset from UTC in effect at `coord` at the given instant, daylight saving time included.
+pub fn utc_offset_at(coord: &Coord, unix_timestamp: i64) -> i64 {
+    let utc = DateTime::from_timestamp(unix_timestamp, 0).map(|d| d.naive_utc());
+    match (time_zone_at(coord), utc) {
+        (Some(zone), Some(utc)) => {
+            zone.offset_from_utc_datetime(&utc).fix().local_minus_utc() as i64
+        }
+        _ => nautical_offset_seconds(coord),
+    }
+}
+
+/// Offset from UTC at `coord` when the clocks there showed `local`. A time skipped by the
+/// spring change gets the offset from before it, as a camera not yet set forward would.
+pub fn local_utc_offset_at(coord: &Coord, local: MyDateTime) -> i64 {
+    let (Some(zone), Some(naive)) = (time_zone_at(coord), local.to_naive()) else {
+        return nautical_offset_seconds(coord);
+    };
+
+    let offset = zone
+        .offset_from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| {
+            let before_change = naive - TimeDelta::hours(1);
+            zone.offset_from_local_datetime(&before_change).earliest()
+        });
+    offset.map_or_else(
+        || nautical_offset_seconds(coord),
+        |offset| offset.fix().local_minus_utc() as i64,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MADRID: Coord = Coord {
+        lat: 40.4168,
+        lon: -3.7038,
+    };
+
+    #[test]
+    fn follows_daylight_saving_time() {
+        // 2023-07-14 22:30 UTC is half past midnight of the next day in Madrid.
+        assert_eq!(utc_offset_at(&MADRID, 1_689_373_800), 7200);
+        // 2023-01-14 22:30 UTC
+        assert_eq!(utc_offset_at(&MADRID, 1_673_735_400), 3600);
+    }
+
+    #[test]
+    fn uses_the_civil_zone_rather_than_longitude() {
+        // Madrid lies west of Greenwich but keeps Central European Time.
+        let local = MyDateTime {
+            year: 2023,
+            month: 1,
+            day: 14,
+            hour: 23,
+            minute: 30,
+            second: 0,
+        };
+        assert_eq!(local_utc_offset_at(&MADRID, local), 3600);
+    }
+
+    #[test]
+    fn keeps_the_winter_offset_in_the_spring_gap() {
+        // Clocks in Madrid jumped from 02:00 to 03:00 on 2023-03-26.
+        let local = MyDateTime {
+            year: 2023,
+            month: 3,
+            day: 26,
+            hour: 2,
+            minute: 30,
+            second: 0,
+        };
+        assert_eq!(local_utc_offset_at(&MADRID, local), 3600);
+    }
+
+    #[test]
+    fn falls_back_to_nautical_zones_at_sea() {
+        let mid_pacific = Coord {
+            lat: 0.,
+            lon: -150.,
+        };
+        assert_eq!(utc_offset_at(&mid_pacific, 1_689_373_800), -10 * 3600);
+    }
+}
@@ -4,7 +4,7 @@ use crate::types::*;
 
 pub fn to_serde_tree(tree: Tree2) -> SerdeTree {
     match tree {
-        Tree2::Node(convex_hull, number_of_leaves, children) => {
+        Tree2::Node(convex_hull, number_of_leaves, children, details) => {
             let serde_children: LinkedList<_> = children
                 .into_iter()
                 .map(|n| Box::new(to_serde_tree(*n)))
@@ -13,16 +13,18 @@ pub fn to_serde_tree(tree: Tree2) -> SerdeTree {
                 convex_hull,
                 number_of_leaves,
                 children: serde_children,
+                details,
             };
             SerdeTree {
                 node: Some(serde_node),
                 leaf: None,
             }
         }
-        Tree2::Leaf(filepath, coord) => {
+        Tree2::Leaf(filepath, coord, details) => {
             let serde_leaf = SerdeLeaf {
                 filepath,
                 coord,
+                details,
             };
             SerdeTree {
                 node: None,
@@ -0,0 +1,28 @@
+use std::convert::Infallible;
+
+use actix_web::web::Bytes;
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+/// Tells connected browsers, as server-sent events, that the tree has been recomputed.
+pub struct TreeEvents(broadcast::Sender<()>);
+
+impl Default for TreeEvents {
+    fn default() -> Self {
+        TreeEvents(broadcast::channel(16).0)
+    }
+}
+
+impl TreeEvents {
+    pub fn tree_changed(&self) {
+        // Nobody listening is not an error.
+        let _ = self.0.send(());
+    }
+
+    /// An endless `text/event-stream` body. A listener that lagged behind gets a single event
+    /// for all the changes it missed.
+    pub fn subscribe(&self) -> impl Stream<Item = Result<Bytes, Infallible>> {
+        BroadcastStream::new(self.0.subscribe())
+            .map(|_| Ok(Bytes::from_static(b"event: tree_changed\ndata: \n\n")))
+    }
+}
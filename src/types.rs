@@ -1,10 +1,23 @@
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, collections::LinkedList, ops::Add, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, LinkedList},
+    fmt, io,
+    ops::Add,
+    rc::Rc,
+};
 
 #[derive(Default, Clone, Debug, Eq, PartialEq)]
 pub struct Haversine {}
 
 unsafe impl Sync for Haversine {}
+
+/// Distance in meters between points of `[lat, lon, hours since the Unix epoch]`, an hour
+/// apart counting as far as `meters_per_hour` meters apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpaceTime {
+    pub meters_per_hour: f64,
+}
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Copy)]
 pub struct NumberOfLeaves(pub usize);
 
@@ -25,7 +38,7 @@ pub struct Coord {
 #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
 pub struct Filepath(pub String);
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Folder(pub String);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -34,15 +47,278 @@ pub struct Centroid(pub Coord);
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConvexHull2(pub LinkedList<Coord>);
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum GpsError {
+    UnexpectedRef(String),
+    UnexpectedValueType,
+    MissingComponents,
+    NotANumber,
+    OutOfRange(f64, f64),
+}
+
+impl fmt::Display for GpsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpsError::UnexpectedRef(r) => write!(f, "unexpected GPS reference {:?}", r),
+            GpsError::UnexpectedValueType => write!(f, "GPS coordinates are not numbers"),
+            GpsError::MissingComponents => write!(f, "GPS coordinates have no components"),
+            GpsError::NotANumber => write!(f, "GPS coordinates are not finite"),
+            GpsError::OutOfRange(lat, lon) => {
+                write!(f, "GPS coordinates out of range: {}, {}", lat, lon)
+            }
+        }
+    }
+}
+
+/// Why a file did not make it into clustering.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "reason", content = "details", rename_all = "snake_case")]
+pub enum SkipReason {
+    NoGps,
+    InvalidGps(String),
+    UnsupportedFormat,
+    ParseError(String),
+    IoError(String),
+    PermissionDenied,
+}
+
+impl SkipReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkipReason::NoGps => "no_gps",
+            SkipReason::InvalidGps(_) => "invalid_gps",
+            SkipReason::UnsupportedFormat => "unsupported_format",
+            SkipReason::ParseError(_) => "parse_error",
+            SkipReason::IoError(_) => "io_error",
+            SkipReason::PermissionDenied => "permission_denied",
+        }
+    }
+
+    /// Whether the file may be read fine next time without itself changing, e.g. after a
+    /// `chmod`, which leaves its size and modification time alone.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SkipReason::IoError(_) | SkipReason::PermissionDenied)
+    }
+}
+
+impl From<io::Error> for SkipReason {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => SkipReason::PermissionDenied,
+            _ => SkipReason::IoError(e.to_string()),
+        }
+    }
+}
+
+impl From<exif::Error> for SkipReason {
+    fn from(e: exif::Error) -> Self {
+        match e {
+            exif::Error::Io(e) => SkipReason::from(e),
+            e => SkipReason::ParseError(e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub filepath: Filepath,
+
+    #[serde(flatten)]
+    pub reason: SkipReason,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub files_seen: usize,
+    pub files_with_location: usize,
+    /// Number of skipped files per `SkipReason::name`.
+    pub skipped_counts: BTreeMap<String, usize>,
+    pub skipped: Vec<SkippedFile>,
+    /// Files read along with a photo, such as XMP and Takeout sidecars, rather than on
+    /// their own. They are not skipped.
+    pub sidecars: usize,
+    #[serde(skip)]
+    sidecar_files: BTreeSet<String>,
+}
+
+impl ScanReport {
+    pub fn skip(&mut self, filepath: Filepath, reason: SkipReason) {
+        *self
+            .skipped_counts
+            .entry(reason.name().to_string())
+            .or_default() += 1;
+        self.skipped.push(SkippedFile { filepath, reason });
+    }
+
+    pub fn add_sidecar(&mut self, filepath: Filepath) {
+        self.sidecar_files.insert(filepath.0);
+        self.sidecars = self.sidecar_files.len();
+    }
+
+    pub fn sidecar_files(&self) -> impl Iterator<Item = &str> {
+        self.sidecar_files.iter().map(String::as_str)
+    }
+
+    /// Removes the entries recorded for `filepath`, returning whether there were any.
+    pub fn forget(&mut self, filepath: &Filepath) -> bool {
+        let was_sidecar = self.sidecar_files.remove(&filepath.0);
+        self.sidecars = self.sidecar_files.len();
+
+        let before = self.skipped.len();
+        let counts = &mut self.skipped_counts;
+        self.skipped.retain(|skipped| {
+            if skipped.filepath.0 != filepath.0 {
+                return true;
+            }
+            if let Some(count) = counts.get_mut(skipped.reason.name()) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(skipped.reason.name());
+                }
+            }
+            false
+        });
+        was_sidecar || self.skipped.len() != before
+    }
+}
+
+/// Everything the tree carries about a leaf besides its path and position.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LeafDetails {
+    /// The position was interpolated from a GPX track rather than read from the file.
+    pub interpolated: bool,
+    /// The scanned folder the file was found in.
+    pub root: Folder,
+    /// Set on all but one of files with identical contents, to the one that is kept.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<Filepath>,
+    /// Capture time in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// How much of an outlier HDBSCAN found the photo, from 0 to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outlier_score: Option<f64>,
+    /// Zone the photo was taken in, in seconds east of UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utc_offset_seconds: Option<i64>,
+    pub file_size: u64,
+
+    #[serde(flatten)]
+    pub properties: PhotoProperties,
+}
+
+/// Camera and image properties read from the file, all optional.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PhotoProperties {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lens: Option<String>,
+    /// Size in pixels as stored, before `orientation` is applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// EXIF orientation, 1 to 8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u16>,
+    /// Meters above sea level.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+}
+
+impl PhotoProperties {
+    pub fn or(self, other: PhotoProperties) -> PhotoProperties {
+        PhotoProperties {
+            make: self.make.or(other.make),
+            model: self.model.or(other.model),
+            lens: self.lens.or(other.lens),
+            width: self.width.or(other.width),
+            height: self.height.or(other.height),
+            orientation: self.orientation.or(other.orientation),
+            altitude: self.altitude.or(other.altitude),
+        }
+    }
+}
+
+/// A file that made it into clustering.
+#[derive(Clone, Debug)]
+pub struct Photo {
+    pub filepath: Filepath,
+    pub coord: Coord,
+    pub details: LeafDetails,
+    /// Hex SHA-256 of the file.
+    pub content_hash: String,
+    /// Only computed when similar photos are grouped.
+    pub perceptual_hash: Option<u64>,
+    pub date_time: Option<ZonedDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupKind {
+    /// Resized copies, edits and other files that look the same.
+    NearDuplicates,
+    /// Shots taken within seconds of each other at the same place.
+    Burst,
+}
+
+/// Photos clustered as a single point; more than one photo makes a synthetic node.
+#[derive(Clone, Debug)]
+pub struct PhotoGroup {
+    pub photos: Vec<Photo>,
+    pub kind: Option<GroupKind>,
+}
+
+/// Earliest and latest capture time below a node, in seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateRange {
+    pub first: i64,
+    pub last: i64,
+}
+
+impl DateRange {
+    pub fn union(self, other: DateRange) -> DateRange {
+        DateRange {
+            first: self.first.min(other.first),
+            last: self.last.max(other.last),
+        }
+    }
+}
+
+/// Everything the tree carries about a node besides its hull and size.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NodeDetails {
+    /// Set on synthetic nodes holding a group of similar photos.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupKind>,
+    /// Set on the nodes of the suggested flat clustering, none of them below another.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub selected: bool,
+    /// Set on the node holding the photos that belong to no cluster.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unclustered: bool,
+    /// Unset when no photo below the node has a capture time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_range: Option<DateRange>,
+}
+
 pub enum Tree {
-    Node(NumberOfLeaves, LinkedList<Rc<RefCell<Tree>>>),
-    Leaf(Filepath, Coord),
+    Node(NumberOfLeaves, LinkedList<Rc<RefCell<Tree>>>, NodeDetails),
+    Leaf(Filepath, Coord, LeafDetails),
 }
 
 #[derive(Clone, Debug)]
 pub enum Tree2 {
-    Node(ConvexHull2, NumberOfLeaves, LinkedList<Box<Tree2>>),
-    Leaf(Filepath, Coord),
+    Node(
+        ConvexHull2,
+        NumberOfLeaves,
+        LinkedList<Box<Tree2>>,
+        NodeDetails,
+    ),
+    Leaf(Filepath, Coord, LeafDetails),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,12 +326,18 @@ pub struct SerdeNode {
     pub convex_hull: ConvexHull2,
     pub number_of_leaves: NumberOfLeaves,
     pub children: LinkedList<Box<SerdeTree>>,
+
+    #[serde(flatten)]
+    pub details: NodeDetails,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SerdeLeaf {
     pub filepath: Filepath,
     pub coord: Coord,
+
+    #[serde(flatten)]
+    pub details: LeafDetails,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -67,15 +349,381 @@ pub struct SerdeTree {
     pub leaf: Option<SerdeLeaf>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComputeClustersResponse {
+    pub tree: SerdeTree,
+    pub report: ScanReport,
+    /// The parameters the tree was clustered with.
+    pub parameters: ClusteringParameters,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Hash)]
 pub struct Cluster(pub Vec<Filepath>);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Clusters(pub Vec<Cluster>);
 
+fn default_max_gap_seconds() -> i64 {
+    600
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Geotagging {
+    pub gpx_files: Vec<Filepath>,
+
+    /// How far the camera clock is ahead of UTC, e.g. 7200 for a camera set to CEST. Only
+    /// used for photos that do not record their time zone.
+    #[serde(default)]
+    pub camera_clock_offset_seconds: i64,
+
+    /// Photos further than this from the nearest track point are left without position.
+    /// Between points further apart than this the position is not interpolated.
+    #[serde(default = "default_max_gap_seconds")]
+    pub max_gap_seconds: i64,
+}
+
+fn default_builtin_excludes() -> bool {
+    true
+}
+
+/// Which entries below the folder a scan reads.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalkOptions {
+    /// Glob patterns, relative to the folder, of the files to read; all files when empty.
+    /// A pattern without a `/` matches the name in any directory.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Glob patterns of the files and directories to skip, written like `include`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Entries directly in the folder are at depth 1.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
+    /// Skips the `categorized` output of `reorganize`, `@eaDir` and `.thumbnails`
+    /// directories and hidden entries.
+    #[serde(default = "default_builtin_excludes")]
+    pub builtin_excludes: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            include: vec![],
+            exclude: vec![],
+            max_depth: None,
+            follow_symlinks: false,
+            builtin_excludes: default_builtin_excludes(),
+        }
+    }
+}
+
+fn default_max_hash_distance() -> u32 {
+    8
+}
+
+fn default_burst_seconds() -> i64 {
+    2
+}
+
+/// How photos are grouped into near-duplicates and bursts before clustering.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimilarityOptions {
+    /// Largest number of differing bits between the perceptual hashes of near-duplicates.
+    #[serde(default = "default_max_hash_distance")]
+    pub max_hash_distance: u32,
+
+    /// Longest time between two shots of a burst.
+    #[serde(default = "default_burst_seconds")]
+    pub burst_seconds: i64,
+}
+
+fn default_min_samples() -> usize {
+    2
+}
+
+fn default_min_cluster_size() -> usize {
+    4
+}
+
+fn default_alpha() -> f64 {
+    1.
+}
+
+fn default_meters_per_hour() -> f64 {
+    1000.
+}
+
+/// What clustering measures the distance between photos by.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Only where the photos were taken.
+    #[default]
+    Distance,
+    /// Where and when, so that visits to the same place years apart stay apart.
+    SpaceTime {
+        #[serde(default = "default_meters_per_hour")]
+        meters_per_hour: f64,
+    },
+}
+
+/// How photos are clustered. Only HDBSCAN builds a hierarchy; the others give flat
+/// clusters under a single root.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClusteringAlgorithm {
+    #[default]
+    Hdbscan,
+    /// Photos with `min_samples` others within `eps_meters` and what they reach.
+    Dbscan { eps_meters: f64 },
+    /// Like DBSCAN, but ordering photos by reachability first.
+    Optics { eps_meters: f64 },
+    /// Every photo joins the first cluster whose first photo is within `radius_meters`.
+    GreedyRadius { radius_meters: f64 },
+}
+
+/// How HDBSCAN picks the suggested flat clusters from its hierarchy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterSelection {
+    /// The most stable clusters, which tends to favour a few large ones.
+    #[default]
+    ExcessOfMass,
+    /// The clusters at the bottom of the hierarchy, many and small.
+    Leaf,
+}
+
+/// Clustering parameters. The defaults suit trips; a city walk wants smaller clusters.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClusteringParameters {
+    /// Neighbours a point needs to be dense; higher values make more photos noise.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+
+    /// Fewest points that form a cluster of their own.
+    #[serde(default = "default_min_cluster_size")]
+    pub min_cluster_size: usize,
+
+    /// Scales distances between dense areas; above 1 splits clusters more readily.
+    #[serde(default = "default_alpha")]
+    pub alpha: f64,
+
+    #[serde(default)]
+    pub metric: DistanceMetric,
+
+    #[serde(default)]
+    pub algorithm: ClusteringAlgorithm,
+
+    #[serde(default)]
+    pub cluster_selection: ClusterSelection,
+}
+
+impl Default for ClusteringParameters {
+    fn default() -> Self {
+        ClusteringParameters {
+            min_samples: default_min_samples(),
+            min_cluster_size: default_min_cluster_size(),
+            alpha: default_alpha(),
+            metric: DistanceMetric::default(),
+            algorithm: ClusteringAlgorithm::default(),
+            cluster_selection: ClusterSelection::default(),
+        }
+    }
+}
+
+impl ClusteringParameters {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_samples < 1 {
+            return Err(String::from("'min_samples' has to be at least 1."));
+        }
+        if self.min_cluster_size < 2 {
+            return Err(String::from("'min_cluster_size' has to be at least 2."));
+        }
+        if !(self.alpha.is_finite() && self.alpha > 0.) {
+            return Err(String::from("'alpha' has to be a positive number."));
+        }
+        if let DistanceMetric::SpaceTime { meters_per_hour } = self.metric {
+            if !(meters_per_hour.is_finite() && meters_per_hour > 0.) {
+                return Err(String::from(
+                    "'meters_per_hour' has to be a positive number.",
+                ));
+            }
+        }
+        match self.algorithm {
+            ClusteringAlgorithm::Dbscan { eps_meters }
+            | ClusteringAlgorithm::Optics { eps_meters }
+                if !(eps_meters.is_finite() && eps_meters > 0.) =>
+            {
+                Err(String::from("'eps_meters' has to be a positive number."))
+            }
+            ClusteringAlgorithm::GreedyRadius { radius_meters }
+                if !(radius_meters.is_finite() && radius_meters > 0.) =>
+            {
+                Err(String::from("'radius_meters' has to be a positive number."))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavePreset {
+    pub name: String,
+    pub parameters: ClusteringParameters,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeletePreset {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComputeClusters {
+    /// Folders scanned together into one tree.
+    pub folders: Vec<Folder>,
+
+    #[serde(default)]
+    pub geotagging: Option<Geotagging>,
+
+    /// Number of files read in parallel; defaults to the number of CPUs.
+    #[serde(default)]
+    pub scan_concurrency: Option<usize>,
+
+    /// Ignores the metadata cached by earlier scans and reads every file again.
+    #[serde(default)]
+    pub rebuild_cache: bool,
+
+    /// Keeps watching the folder after the scan and recomputes the tree when files change.
+    #[serde(default)]
+    pub watch: bool,
+
+    #[serde(default)]
+    pub walk: WalkOptions,
+
+    /// Groups near-duplicates and bursts under synthetic nodes. Needs every photo decoded
+    /// once, so it is off unless requested.
+    #[serde(default)]
+    pub group_similar: Option<SimilarityOptions>,
+
+    #[serde(default)]
+    pub clustering: ClusteringParameters,
+}
+
+/// Reorganizes the last scanned tree along its suggested clusters instead of hand-picked ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AutoReorganize {
+    /// One of the scanned folders; the `categorized` output is created in it.
+    pub folder: Folder,
+    pub dryrun: bool,
+
+    #[serde(default)]
+    pub move_duplicates: bool,
+
+    /// Moves all photos of a group of similar photos but the first to a subfolder.
+    #[serde(default)]
+    pub collapse_groups: bool,
+
+    /// Moves the photos outside the suggested clusters to `categorized/misc`; otherwise
+    /// they stay where they are.
+    #[serde(default)]
+    pub move_unclustered: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClearCache {
+    pub folder: Folder,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThumbnailQuery {
+    /// Longest edge in pixels.
+    pub size: Option<u32>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Commit {
     pub clusters: Clusters,
+    /// One of the scanned folders; the `categorized` output is created in it.
     pub folder: Folder,
     pub dryrun: bool,
+
+    /// Keeps one of files with identical contents in its cluster and moves the other
+    /// copies to `categorized/duplicates`.
+    #[serde(default)]
+    pub move_duplicates: bool,
+
+    /// Groups of similar photos, earliest first. Only the first photo of each lands among
+    /// the other photos of its cluster, the rest go to a subfolder named after it.
+    #[serde(default)]
+    pub collapsed_groups: Vec<Cluster>,
+
+    /// Photos in no cluster, moved to `categorized/misc` under their own names.
+    #[serde(default)]
+    pub misc: Vec<Filepath>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MyDateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl From<exif::DateTime> for MyDateTime {
+    fn from(f: exif::DateTime) -> Self {
+        MyDateTime {
+            year: f.year,
+            month: f.month,
+            day: f.day,
+            hour: f.hour,
+            minute: f.minute,
+            second: f.second,
+        }
+    }
+}
+
+/// A capture time as shown on the clock where it was taken, with that clock's offset from
+/// UTC. Ordered by the instant it denotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZonedDateTime {
+    pub local: MyDateTime,
+    /// Seconds east of UTC.
+    pub offset_seconds: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PhotoMetadata {
+    pub coord: Option<Coord>,
+    /// Capture time on the camera clock.
+    pub date_time: Option<MyDateTime>,
+    /// Zone of `date_time` in seconds east of UTC, when the file records it.
+    pub utc_offset_seconds: Option<i64>,
+    /// Capture time in UTC, from GPS tags or video headers.
+    pub utc_date_time: Option<MyDateTime>,
+    /// Set when GPS tags are present but cannot be turned into a position.
+    pub gps_error: Option<GpsError>,
+    pub properties: PhotoProperties,
+}
+
+impl PhotoMetadata {
+    /// Fills the fields missing in `self` with the ones from `other`.
+    pub fn or(self, other: PhotoMetadata) -> PhotoMetadata {
+        PhotoMetadata {
+            coord: self.coord.or(other.coord),
+            date_time: self.date_time.or(other.date_time),
+            utc_offset_seconds: self.utc_offset_seconds.or(other.utc_offset_seconds),
+            utc_date_time: self.utc_date_time.or(other.utc_date_time),
+            gps_error: self.gps_error.or(other.gps_error),
+            properties: self.properties.or(other.properties),
+        }
+    }
 }
@@ -6,17 +6,28 @@ import ExpandMoreIcon from '@mui/icons-material/ExpandMore';
 import ChevronRightIcon from '@mui/icons-material/ChevronRight';
 import { TreeView } from '@mui/x-tree-view/TreeView';
 import { TreeItem } from '@mui/x-tree-view/TreeItem';
-import { Button, Checkbox, Container, Dialog, DialogActions, DialogContent, DialogContentText, DialogTitle, FormControlLabel, FormGroup, Grid, ImageList, ImageListItem, LinearProgress, TextField } from '@mui/material';
+import { Button, Checkbox, Container, Dialog, DialogActions, DialogContent, DialogContentText, DialogTitle, FormControlLabel, FormGroup, Grid, ImageList, ImageListItem, LinearProgress, MenuItem, TextField } from '@mui/material';
 import { CheckBox } from '@mui/icons-material';
 
 interface Node {
   convex_hull: Coord[]
   number_of_leaves: number
   children: Tree[]
+  group?: "near_duplicates" | "burst"
+  unclustered?: boolean
+  // part of the suggested flat clustering
+  selected?: boolean
+  date_range?: DateRange
 
   center?: Coord
 }
 
+// seconds since the Unix epoch
+interface DateRange {
+  first: number
+  last: number
+}
+
 interface Coord {
   lat: number,
   lon: number
@@ -25,6 +36,20 @@ interface Coord {
 interface Leaf {
   filepath: string
   coord: Coord
+  interpolated: boolean
+  root: string
+  duplicate_of?: string
+  timestamp?: number
+  outlier_score?: number
+  utc_offset_seconds?: number
+  file_size: number
+  make?: string
+  model?: string
+  lens?: string
+  width?: number
+  height?: number
+  orientation?: number
+  altitude?: number
 }
 interface Tree {
   node?: Node
@@ -35,10 +60,102 @@ interface Tree {
 
 type Clusters = Array<Array<string>>
 
+type ScanReport = {
+  files_seen: number
+  files_with_location: number
+  skipped_counts: { [reason: string]: number }
+  sidecars: number
+}
+
+type DistanceMetric = { kind: "distance" } | { kind: "space_time", meters_per_hour: number }
+
+type ClusteringAlgorithm =
+  { kind: "hdbscan" } |
+  { kind: "dbscan", eps_meters: number } |
+  { kind: "optics", eps_meters: number } |
+  { kind: "greedy_radius", radius_meters: number }
+
+type ClusteringParameters = {
+  min_samples: number
+  min_cluster_size: number
+  alpha: number
+  metric: DistanceMetric
+  algorithm: ClusteringAlgorithm
+  cluster_selection: "excess_of_mass" | "leaf"
+}
+
+function defaultAlgorithm(kind: string): ClusteringAlgorithm {
+  switch (kind) {
+    case "dbscan": return { kind: "dbscan", eps_meters: 500 };
+    case "optics": return { kind: "optics", eps_meters: 500 };
+    case "greedy_radius": return { kind: "greedy_radius", radius_meters: 500 };
+    default: return { kind: "hdbscan" };
+  }
+}
+
+function getAlgorithmRadius(algorithm: ClusteringAlgorithm): number | undefined {
+  switch (algorithm.kind) {
+    case "dbscan":
+    case "optics": return algorithm.eps_meters;
+    case "greedy_radius": return algorithm.radius_meters;
+    default: return undefined;
+  }
+}
+
+function withAlgorithmRadius(algorithm: ClusteringAlgorithm, meters: number): ClusteringAlgorithm {
+  switch (algorithm.kind) {
+    case "dbscan":
+    case "optics": return { ...algorithm, eps_meters: meters };
+    case "greedy_radius": return { ...algorithm, radius_meters: meters };
+    default: return algorithm;
+  }
+}
+
+type Presets = { [name: string]: ClusteringParameters }
+
+type ComputeClustersResponse = {
+  tree: Tree
+  report: ScanReport
+  parameters: ClusteringParameters
+}
+
+type Geotagging = {
+  gpx_files: string[]
+}
+
+type WalkOptions = {
+  include?: string[]
+  exclude?: string[]
+  max_depth?: number
+  follow_symlinks?: boolean
+  builtin_excludes?: boolean
+}
+
+type ComputeClusters = {
+  folders: string[]
+  geotagging?: Geotagging
+  rebuild_cache?: boolean
+  watch?: boolean
+  walk?: WalkOptions
+  group_similar?: {}
+  clustering?: ClusteringParameters
+}
+
 type Commit = {
   clusters: Clusters
   folder: string
   dryrun: boolean
+  move_duplicates: boolean
+  collapsed_groups: Clusters
+  misc: string[]
+}
+
+type AutoReorganize = {
+  folder: string
+  dryrun: boolean
+  move_duplicates: boolean
+  collapse_groups: boolean
+  move_unclustered: boolean
 }
 
 function getId(tree: Tree) {
@@ -167,11 +284,43 @@ interface MyTreeViewProps {
   tree: Tree
 }
 
+// the date on the clock where the photo was taken, UTC when the zone is unknown
+function formatDate(timestamp: number, utcOffsetSeconds: number = 0): string {
+  return new Date((timestamp + utcOffsetSeconds) * 1000).toISOString().slice(0, 10);
+}
+
+function getDateRangeLabel(dateRange: DateRange): string {
+  const first = formatDate(dateRange.first);
+  const last = formatDate(dateRange.last);
+  return first == last ? first : first + " to " + last;
+}
+
 function getLabel(tree: Tree): string {
   if (tree.node != undefined) {
-    return tree.node.number_of_leaves.toString();
+    let label = tree.node.number_of_leaves.toString();
+    if (tree.node.group == "burst") {
+      label = "Burst of " + label;
+    } else if (tree.node.group == "near_duplicates") {
+      label += " similar";
+    } else if (tree.node.unclustered) {
+      label = "Unclustered " + label;
+    }
+    if (tree.node.selected) {
+      label = "Suggested cluster of " + label;
+    }
+    if (tree.node.date_range != undefined) {
+      label += " (" + getDateRangeLabel(tree.node.date_range) + ")";
+    }
+    return label;
   } else if (tree.leaf != undefined) {
-    return tree.leaf.filepath;
+    let label = tree.leaf.interpolated ? tree.leaf.filepath + " (GPX)" : tree.leaf.filepath;
+    if (tree.leaf.timestamp != undefined) {
+      label += " " + formatDate(tree.leaf.timestamp, tree.leaf.utc_offset_seconds);
+    }
+    if (tree.leaf.duplicate_of != undefined) {
+      label += " (duplicate of " + tree.leaf.duplicate_of + ")";
+    }
+    return label;
   }
   return ""
 }
@@ -331,11 +480,13 @@ interface MyComponentProps {
 
 interface AppProps {
   tree: Tree
-  onCommit: (treeView: MyTreeView) => void
+  folders: string[]
+  onCommit: (treeView: MyTreeView, folder: string, moveDuplicates: boolean, collapseGroups: boolean, moveUnclustered: boolean) => void
+  onAutoCommit: (folder: string, moveDuplicates: boolean, collapseGroups: boolean, moveUnclustered: boolean) => void
 }
 
 interface FolderSelectionDialogProps {
-  onChosen: (folder: string) => void
+  onChosen: (request: ComputeClusters) => void
 }
 
 interface ErrorMessageDialogProps {
@@ -430,10 +581,69 @@ function ErrorMessageDialog({ message }: ErrorMessageDialogProps) {
   );
 }
 
+type ScanProgress = {
+  running: boolean
+  files_seen: number
+  files_parsed: number
+  files_with_gps: number
+}
+
+const defaultClusteringParameters: ClusteringParameters = {
+  min_samples: 2, min_cluster_size: 4, alpha: 1, metric: { kind: "distance" }, algorithm: { kind: "hdbscan" },
+  cluster_selection: "excess_of_mass"
+};
+
 function FolderSelectionDialog({ onChosen }: FolderSelectionDialogProps) {
   const [dialogOpen, setDialogOpen] = React.useState(true);
+  const [progress, setProgress] = React.useState<ScanProgress | undefined>(undefined);
+  const [presets, setPresets] = React.useState<Presets>({});
+  const [parameters, setParameters] = React.useState<ClusteringParameters>(defaultClusteringParameters);
+  const [presetName, setPresetName] = React.useState("");
+
+  const loadPresets = async () => {
+    const response = await fetch(`/presets`);
+    if (response.ok) {
+      setPresets(await response.json() as Presets);
+    }
+  };
 
-  let circularProgress = dialogOpen ? <></> : <LinearProgress />;
+  React.useEffect(() => { loadPresets(); }, []);
+
+  const savePreset = async () => {
+    const response = await fetch(`/save_preset`, {
+      method: "POST",
+      body: JSON.stringify({ name: presetName, parameters: parameters })
+    });
+    if (response.ok) {
+      loadPresets();
+    } else {
+      alert(await response.text());
+    }
+  };
+
+  const setParameter = (name: keyof ClusteringParameters) => (event: React.ChangeEvent<HTMLInputElement>) => {
+    setParameters({ ...parameters, [name]: Number(event.target.value) });
+  };
+
+  React.useEffect(() => {
+    if (dialogOpen) {
+      return;
+    }
+
+    const interval = setInterval(async () => {
+      const response = await fetch(`/scan_progress`);
+      if (response.ok) {
+        setProgress(await response.json() as ScanProgress);
+      }
+    }, 1000);
+
+    return () => clearInterval(interval);
+  }, [dialogOpen]);
+
+  const progressText = progress == undefined || !progress.running ? "" :
+    `${progress.files_parsed} / ${progress.files_seen} files read, ${progress.files_with_gps} with location`;
+
+  let circularProgress = dialogOpen ? <></> : <><LinearProgress />{progressText}</>;
 
   const handleClose = () => {
     setDialogOpen(false);
@@ -450,9 +660,32 @@ function FolderSelectionDialog({ onChosen }: FolderSelectionDialogProps) {
             event.preventDefault();
             const formData = new FormData(event.currentTarget);
             const formJson = Object.fromEntries((formData as any).entries());
-            const directory = formJson.directory;
-
-            onChosen(directory);
+            const directories = (formJson.directories as string)
+              .split("\n")
+              .map(d => d.trim())
+              .filter(d => d.length != 0);
+            const splitList = (list: string) => list
+              .split(",")
+              .map(f => f.trim())
+              .filter(f => f.length != 0);
+            const gpxFiles = splitList(formJson.gpx_files as string);
+
+            const request: ComputeClusters = {
+              folders: directories,
+              rebuild_cache: formJson.rebuild_cache == "on",
+              watch: formJson.watch == "on",
+              group_similar: formJson.group_similar == "on" ? {} : undefined,
+              clustering: parameters,
+              walk: {
+                include: splitList(formJson.include as string),
+                exclude: splitList(formJson.exclude as string)
+              }
+            };
+            if (gpxFiles.length != 0) {
+              request.geotagging = { gpx_files: gpxFiles };
+            }
+
+            onChosen(request);
 
             handleClose();
           },
@@ -461,19 +694,171 @@ function FolderSelectionDialog({ onChosen }: FolderSelectionDialogProps) {
         <DialogTitle>Select directory</DialogTitle>
         <DialogContent>
           <DialogContentText>
-            Absolute path to a directory where images are searched for recursively
+            Absolute paths to directories where images are searched for recursively, one per line
           </DialogContentText>
           <TextField
             autoFocus
             required
             margin="dense"
             id="name"
-            name="directory"
-            label="Absolute path to directory"
+            name="directories"
+            label="Absolute paths to directories"
+            type="text"
+            multiline
+            fullWidth
+            variant="standard"
+          />
+          <TextField
+            margin="dense"
+            id="gpx_files"
+            name="gpx_files"
+            label="GPX track files to geotag photos without GPS (comma separated, optional)"
             type="text"
             fullWidth
             variant="standard"
           />
+          <TextField
+            margin="dense"
+            id="include"
+            name="include"
+            label="Only read files matching these patterns, e.g. *.jpg (comma separated, optional)"
+            type="text"
+            fullWidth
+            variant="standard"
+          />
+          <TextField
+            margin="dense"
+            id="exclude"
+            name="exclude"
+            label="Skip files and directories matching these patterns, e.g. Screenshots (comma separated, optional)"
+            type="text"
+            fullWidth
+            variant="standard"
+          />
+          <FormControlLabel
+            control={<Checkbox name="group_similar" />}
+            label="Group near-duplicates and bursts (decodes every photo)"
+          />
+          <FormControlLabel
+            control={<Checkbox name="rebuild_cache" />}
+            label="Read all files again instead of using the metadata cache"
+          />
+          <FormControlLabel
+            control={<Checkbox name="watch" />}
+            label="Watch the directory and update the clusters when files change"
+          />
+          <TextField
+            select
+            margin="dense"
+            label="Clustering preset"
+            value={Object.keys(presets).find(name => JSON.stringify(presets[name]) == JSON.stringify(parameters)) ?? ""}
+            onChange={event => setParameters(presets[event.target.value])}
+            fullWidth
+            variant="standard"
+          >
+            {Object.keys(presets).map(name => <MenuItem key={name} value={name}>{name}</MenuItem>)}
+          </TextField>
+          <TextField
+            margin="dense"
+            label="Minimum samples"
+            type="number"
+            value={parameters.min_samples}
+            onChange={setParameter("min_samples")}
+            variant="standard"
+          />
+          <TextField
+            margin="dense"
+            label="Minimum cluster size"
+            type="number"
+            value={parameters.min_cluster_size}
+            onChange={setParameter("min_cluster_size")}
+            variant="standard"
+          />
+          <TextField
+            margin="dense"
+            label="Alpha"
+            type="number"
+            value={parameters.alpha}
+            onChange={setParameter("alpha")}
+            variant="standard"
+          />
+          <TextField
+            select
+            margin="dense"
+            label="Algorithm"
+            value={parameters.algorithm.kind}
+            onChange={event => setParameters({ ...parameters, algorithm: defaultAlgorithm(event.target.value) })}
+            fullWidth
+            variant="standard"
+          >
+            <MenuItem value="hdbscan">HDBSCAN (hierarchy)</MenuItem>
+            <MenuItem value="dbscan">DBSCAN</MenuItem>
+            <MenuItem value="optics">OPTICS</MenuItem>
+            <MenuItem value="greedy_radius">Fixed radius</MenuItem>
+          </TextField>
+          {parameters.algorithm.kind == "hdbscan" &&
+            <TextField
+              select
+              margin="dense"
+              label="Suggested clusters"
+              value={parameters.cluster_selection}
+              onChange={event => setParameters({
+                ...parameters,
+                cluster_selection: event.target.value as ClusteringParameters["cluster_selection"]
+              })}
+              fullWidth
+              variant="standard"
+            >
+              <MenuItem value="excess_of_mass">Most stable</MenuItem>
+              <MenuItem value="leaf">Smallest</MenuItem>
+            </TextField>}
+          {getAlgorithmRadius(parameters.algorithm) != undefined &&
+            <TextField
+              margin="dense"
+              label="Radius in meters"
+              type="number"
+              value={getAlgorithmRadius(parameters.algorithm)}
+              onChange={event => setParameters({
+                ...parameters,
+                algorithm: withAlgorithmRadius(parameters.algorithm, Number(event.target.value))
+              })}
+              variant="standard"
+            />}
+          <TextField
+            select
+            margin="dense"
+            label="Distance"
+            value={parameters.metric.kind}
+            onChange={event => setParameters({
+              ...parameters,
+              metric: event.target.value == "space_time" ? { kind: "space_time", meters_per_hour: 1000 } : { kind: "distance" }
+            })}
+            fullWidth
+            variant="standard"
+          >
+            <MenuItem value="distance">Where the photos were taken</MenuItem>
+            <MenuItem value="space_time">Where and when the photos were taken</MenuItem>
+          </TextField>
+          {parameters.metric.kind == "space_time" &&
+            <TextField
+              margin="dense"
+              label="Meters counted per hour apart"
+              type="number"
+              value={parameters.metric.meters_per_hour}
+              onChange={event => setParameters({
+                ...parameters,
+                metric: { kind: "space_time", meters_per_hour: Number(event.target.value) }
+              })}
+              variant="standard"
+            />}
+          <TextField
+            margin="dense"
+            label="Save these parameters as"
+            value={presetName}
+            onChange={event => setPresetName(event.target.value)}
+            variant="standard"
+          />
+          <Button onClick={savePreset} disabled={presetName.trim().length == 0}>Save preset</Button>
         </DialogContent>
         <DialogActions>
           <Button type="submit">Ok</Button>
@@ -486,17 +871,39 @@ function FolderSelectionDialog({ onChosen }: FolderSelectionDialogProps) {
 
 type Context = {
   tree: Tree,
-  folder: string
+  folders: string[],
+  watch: boolean
 }
 
 export default function App() {
   const [ctx, setCtx] = React.useState<Context | undefined>(undefined);
   const [errorMessage, setErrorMessage] = React.useState<string | undefined>(undefined);
 
-  async function onChosen(folder: string) {
+  const watchedFolders = ctx != undefined && ctx.watch ? ctx.folders.join("\n") : undefined;
+  React.useEffect(() => {
+    if (watchedFolders == undefined) {
+      return;
+    }
+
+    const events = new EventSource("/tree_events");
+    events.addEventListener("tree_changed", async () => {
+      const response = await fetch(`/tree`);
+      if (!response.ok) {
+        console.log("\"tree\" failed: " + await response.text());
+        return;
+      }
+      const { tree } = await response.json() as ComputeClustersResponse;
+      setCtx(current => current == undefined ? undefined : { ...current, tree });
+    });
+
+    return () => events.close();
+  }, [watchedFolders]);
+
+  async function onChosen(request: ComputeClusters) {
+    const folders = request.folders;
     const response = await fetch(`/compute_clusters`, {
       method: "POST",
-      body: folder
+      body: JSON.stringify(request)
     });
 
     if (!response.ok) {
@@ -505,59 +912,82 @@ export default function App() {
       setErrorMessage(msg);
     } else {
       const json = await response.json();
-      const tree = json as Tree;
+      const { tree, report } = json as ComputeClustersResponse;
+      console.log(`Scanned ${report.files_seen} files, ${report.files_with_location} with location, ${report.sidecars} sidecars, skipped: ${JSON.stringify(report.skipped_counts)}`);
       setCtx({
         tree,
-        folder
+        folders,
+        watch: request.watch ?? false
       });
     }
   }
 
-  async function onCommit(treeView: MyTreeView) {
+  async function reorganizeWith(endpoint: string, request: Commit | AutoReorganize) {
+    let asJsonString = JSON.stringify(request);
+    const response = await fetch(endpoint, {
+      method: "POST",
+      body: asJsonString
+    });
+    if (!response.ok) {
+      console.log(`"${endpoint}" failed`);
+      const msg = await response.text();
+      setErrorMessage(msg);
+      return false;
+    } else {
+      const responseText = await response.text();
+      console.log(`Reponse from ${endpoint}: ` + responseText);
+      return true;
+    }
+  }
+
+  // Runs a dry run first so that nothing moves unless every file can be moved.
+  async function reorganizeAfterDryRun(endpoint: string, request: Commit | AutoReorganize) {
+    if (await reorganizeWith(endpoint, request)) {
+      request.dryrun = false;
+      if (await reorganizeWith(endpoint, request)) {
+        setCtx(undefined);
+      } else {
+        setErrorMessage("File operations failed although dry run passed.")
+      }
+    }
+  }
+
+  async function onAutoCommit(folder: string, moveDuplicates: boolean, collapseGroups: boolean, moveUnclustered: boolean) {
+    await reorganizeAfterDryRun(`/reorganize_auto`, {
+      folder,
+      dryrun: true,
+      move_duplicates: moveDuplicates,
+      collapse_groups: collapseGroups,
+      move_unclustered: moveUnclustered
+    });
+  }
+
+  async function onCommit(treeView: MyTreeView, folder: string, moveDuplicates: boolean, collapseGroups: boolean, moveUnclustered: boolean) {
     if (ctx == undefined) {
       return;
     }
 
-    let clusters = getClusters(treeView);
+    const misc = moveUnclustered ? getUnclustered(ctx.tree) : [];
+    let clusters = getClusters(treeView)
+      .map(cluster => cluster.filter(filepath => !misc.includes(filepath)))
+      .filter(cluster => cluster.length != 0);
 
     console.log("on log clusters no: " + clusters.length.toString());
 
-    let reorganizeWith = async (commit: Commit) => {
-      let asJsonString = JSON.stringify(commit);
-      const response = await fetch(`/reorganize`, {
-        method: "POST",
-        body: asJsonString
-      });
-      if (!response.ok) {
-        console.log("\"reorganize\" failed");
-        const msg = await response.text();
-        setErrorMessage(msg);
-        return false;
-      } else {
-        const responseText = await response.text();
-        console.log("Reponse from reorganize: " + responseText);
-        return true;
-      }
-    }
-
     let commit: Commit = {
       clusters: clusters,
-      folder: ctx.folder,
-      dryrun: true
+      folder,
+      dryrun: true,
+      move_duplicates: moveDuplicates,
+      collapsed_groups: collapseGroups ? getGroups(ctx.tree) : [],
+      misc
     }
 
-    if (await reorganizeWith(commit)) {
-      commit.dryrun = false;
-      if (await reorganizeWith(commit)) {
-        setCtx(undefined);
-      } else {
-        setErrorMessage("File operations failed although dry run passed.")
-      }
-    }
+    await reorganizeAfterDryRun(`/reorganize`, commit);
   }
 
   const ui =
-    ctx == undefined ? <></> : <UIActive onCommit={onCommit} tree={ctx.tree}></UIActive>;
+    ctx == undefined ? <></> : <UIActive onCommit={onCommit} onAutoCommit={onAutoCommit} tree={ctx.tree} folders={ctx.folders}></UIActive>;
   const dialog =
     ctx != undefined ? <></> : <FolderSelectionDialog onChosen={onChosen}></FolderSelectionDialog>;
   const errorDialog =
@@ -572,20 +1002,69 @@ export default function App() {
   );
 }
 
+const videoExtensions = ["mp4", "mov", "m4v", "3gp"];
+
+function isVideo(filepath: string): boolean {
+  const extension = filepath.split(".").pop() ?? "";
+  return videoExtensions.includes(extension.toLowerCase());
+}
+
 function getImg(filepath: string): React.JSX.Element {
   let url = "/file/" + filepath;
   let url2 = encodeURI(url);
 
+  if (isVideo(filepath)) {
+    return (
+      <ImageListItem key={filepath}>
+        <video
+          src={url2}
+          controls
+        />
+      </ImageListItem>
+    )
+  }
+
   return (
     <ImageListItem key={filepath}>
-      <img
-        src={url2}
-        loading="lazy"
-      />
+      <a href={encodeURI("/thumbnail/" + filepath) + "?size=2048"} target="_blank">
+        <img
+          src={encodeURI("/thumbnail/" + filepath) + "?size=512"}
+          loading="lazy"
+        />
+      </a>
     </ImageListItem>
   )
 }
 
+// files of every group of similar photos, earliest first
+function getGroups(tree: Tree): Clusters {
+  if (tree.node == undefined) {
+    return [];
+  }
+  if (tree.node.group != undefined) {
+    return [tree.node.children.flatMap(c => c.leaf != undefined ? [c.leaf.filepath] : [])];
+  }
+  return tree.node.children.flatMap(getGroups);
+}
+
+// files below the nodes of photos in no cluster
+function getUnclustered(tree: Tree): string[] {
+  if (tree.node == undefined) {
+    return [];
+  }
+  if (tree.node.unclustered) {
+    return getLeaves(tree);
+  }
+  return tree.node.children.flatMap(getUnclustered);
+}
+
+function getLeaves(tree: Tree): string[] {
+  if (tree.leaf != undefined) {
+    return [tree.leaf.filepath];
+  }
+  return tree.node == undefined ? [] : tree.node.children.flatMap(getLeaves);
+}
+
 type Path = { children: Tree[], index: number }[];
 
 // if tree is a node the path is not empty
@@ -676,17 +1155,25 @@ function ImageViewer({ selectedTree }: ImageViewerProps) {
   );
 }
 
-function UIActive({ onCommit, tree }: AppProps) {
+function UIActive({ onCommit, onAutoCommit, tree, folders }: AppProps) {
   const [selectedNode, setSelectedNode] = React.useState(getId(tree));
   const [treeForImageViewer, setTreeForImageViewer] = React.useState(tree);
   const [currentTreeView, setCurrentTreeView] = React.useState<MyTreeView>({ tree: tree, children: [] });
+  const [targetFolder, setTargetFolder] = React.useState(folders[0]);
+  const [moveDuplicates, setMoveDuplicates] = React.useState(false);
+  const [collapseGroups, setCollapseGroups] = React.useState(false);
+  const [moveUnclustered, setMoveUnclustered] = React.useState(false);
 
   function treeViewChangedCb(treeView: MyTreeView) {
     setCurrentTreeView(treeView);
   }
 
   function onCommitButtonClicked() {
-    onCommit(currentTreeView);
+    onCommit(currentTreeView, targetFolder, moveDuplicates, collapseGroups, moveUnclustered);
+  }
+
+  function onAutoCommitButtonClicked() {
+    onAutoCommit(targetFolder, moveDuplicates, collapseGroups, moveUnclustered);
   }
 
   const id2Tree = buildMap(tree);
@@ -710,6 +1197,29 @@ function UIActive({ onCommit, tree }: AppProps) {
 
       <Grid item xs={6} className='grid'>
         <Button onClick={onCommitButtonClicked} variant="contained">Commit</Button>
+        <Button onClick={onAutoCommitButtonClicked} variant="outlined">Commit suggested clusters</Button>
+        <FormControlLabel
+          control={<Checkbox checked={moveDuplicates} onChange={e => setMoveDuplicates(e.target.checked)} />}
+          label="Move duplicates aside"
+        />
+        <FormControlLabel
+          control={<Checkbox checked={collapseGroups} onChange={e => setCollapseGroups(e.target.checked)} />}
+          label="Collapse similar photos"
+        />
+        <FormControlLabel
+          control={<Checkbox checked={moveUnclustered} onChange={e => setMoveUnclustered(e.target.checked)} />}
+          label="Move unclustered photos to misc"
+        />
+        {folders.length > 1 ?
+          <TextField
+            select
+            size="small"
+            label="Move clusters into"
+            value={targetFolder}
+            onChange={e => setTargetFolder(e.target.value)}
+          >
+            {folders.map(f => <MenuItem key={f} value={f}>{f}</MenuItem>)}
+          </TextField> : <></>}
         <MyTreeView tree={tree} iChangedAsTreeViewCb={treeViewChangedCb} onNodeSelect={id => setSelectedNode(id)}></MyTreeView>
       </Grid>
 
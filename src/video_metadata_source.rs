@@ -0,0 +1,178 @@
+use std::path::Path;
+
+use crate::capture_time::iso8601_offset_seconds;
+use crate::isobmff::*;
+use crate::metadata_source::*;
+use crate::types::*;
+
+/// Seconds between 1904-01-01 (QuickTime epoch) and 1970-01-01.
+const QUICKTIME_EPOCH_OFFSET: i64 = 2082844800;
+
+const ISO6709_KEY: &[u8] = b"com.apple.quicktime.location.ISO6709";
+
+/// Local capture time with its zone, e.g. `2023-06-01T12:00:00+0200`.
+const CREATION_DATE_KEY: &[u8] = b"com.apple.quicktime.creationdate";
+
+const MAKE_KEY: &[u8] = b"com.apple.quicktime.make";
+const MODEL_KEY: &[u8] = b"com.apple.quicktime.model";
+
+/// Parses `+37.7858-122.4064+000.000/` into latitude and longitude.
+fn parse_iso6709(value: &str) -> Option<Coord> {
+    let value = value.trim().trim_end_matches('/');
+    let starts: Vec<usize> = value
+        .char_indices()
+        .filter(|(_, c)| *c == '+' || *c == '-')
+        .map(|(i, _)| i)
+        .collect();
+
+    let lat = value.get(*starts.first()?..*starts.get(1)?)?.parse().ok()?;
+    let lon_end = starts.get(2).copied().unwrap_or(value.len());
+    let lon = value.get(*starts.get(1)?..lon_end)?.parse().ok()?;
+
+    Some(Coord { lat, lon })
+}
+
+/// The optional third component of `+37.7858-122.4064+012.345/`.
+fn parse_iso6709_altitude(value: &str) -> Option<f64> {
+    let value = value.trim().trim_end_matches('/');
+    let starts: Vec<usize> = value
+        .char_indices()
+        .filter(|(_, c)| *c == '+' || *c == '-')
+        .map(|(i, _)| i)
+        .collect();
+
+    value
+        .get(*starts.get(2)?..)?
+        .split("CRS")
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// QuickTime user data: `©xyz` holds a 16-bit length, a 16-bit language code and the text.
+fn get_udta_location(moov: &[u8]) -> Option<&str> {
+    let udta = find_child(moov, b"udta")?;
+    let xyz = find_child(udta, b"\xa9xyz")?;
+    let length = u16::from_be_bytes([*xyz.first()?, *xyz.get(1)?]) as usize;
+    let text = xyz.get(4..4 + length)?;
+
+    std::str::from_utf8(text).ok()
+}
+
+/// Apple metadata: `meta` lists key names in `keys` and their values in `ilst`,
+/// where every item box type is the one-based index of its key.
+fn get_meta_value<'a>(moov: &'a [u8], key: &[u8]) -> Option<&'a str> {
+    let meta = find_child(moov, b"meta")?;
+    // In ISO files `meta` is a full box with 4 bytes of version and flags.
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..)?
+    };
+
+    let keys = find_child(meta, b"keys")?;
+    let entry_count = u32::from_be_bytes(keys.get(4..8)?.try_into().ok()?);
+    let mut rest = keys.get(8..)?;
+    let mut key_index = None;
+    for i in 1..=entry_count {
+        let key_size = u32::from_be_bytes(rest.get(0..4)?.try_into().ok()?) as usize;
+        let key_value = rest.get(8..key_size)?;
+        if key_value == key {
+            key_index = Some(i);
+            break;
+        }
+        rest = rest.get(key_size..)?;
+    }
+
+    let ilst = find_child(meta, b"ilst")?;
+    let item = find_child(ilst, &key_index?.to_be_bytes())?;
+    let data = find_child(item, b"data")?;
+
+    std::str::from_utf8(data.get(8..)?).ok()
+}
+
+/// Size and rotation of the first visual track. `tkhd` ends with the 3x3 transformation
+/// matrix followed by width and height, all fixed point.
+fn extract_track_properties(moov: &[u8]) -> Option<(u32, u32, u16)> {
+    children(moov)
+        .into_iter()
+        .filter(|(box_type, _)| *box_type == b"trak")
+        .find_map(|(_, trak)| {
+            let tkhd = find_child(trak, b"tkhd")?;
+            let fixed = |offset: usize| -> Option<i32> {
+                let start = tkhd.len().checked_sub(offset)?;
+                Some(i32::from_be_bytes(
+                    tkhd.get(start..start + 4)?.try_into().ok()?,
+                ))
+            };
+            let width = fixed(8)? as u32 >> 16;
+            let height = fixed(4)? as u32 >> 16;
+            if width == 0 || height == 0 {
+                return None;
+            }
+
+            let (a, b) = (fixed(44)? >> 16, fixed(40)? >> 16);
+            let orientation = match (a, b) {
+                (0, 1) => 6,
+                (-1, 0) => 3,
+                (0, -1) => 8,
+                _ => 1,
+            };
+            Some((width, height, orientation))
+        })
+}
+
+/// Creation time in UTC, as every muxer writes it.
+fn extract_creation_time(moov: &[u8]) -> Option<MyDateTime> {
+    let mvhd = find_child(moov, b"mvhd")?;
+    let seconds = match mvhd.first()? {
+        0 => u32::from_be_bytes(mvhd.get(4..8)?.try_into().ok()?) as i64,
+        1 => u64::from_be_bytes(mvhd.get(4..12)?.try_into().ok()?) as i64,
+        _ => return None,
+    };
+
+    if seconds == 0 {
+        return None;
+    }
+
+    MyDateTime::from_unix_timestamp(seconds - QUICKTIME_EPOCH_OFFSET)
+}
+
+/// Reads location and creation time from QuickTime / ISO-BMFF videos.
+pub struct VideoMetadataSource {}
+
+impl MetadataSource for VideoMetadataSource {
+    fn extract(&self, path: &Path) -> Result<Option<PhotoMetadata>, SkipReason> {
+        if !has_extension(path, &VIDEO_EXTENSIONS) {
+            return Ok(None);
+        }
+
+        let moov = read_top_level_box(path, b"moov")?
+            .ok_or_else(|| SkipReason::ParseError(String::from("No moov box in video")))?;
+
+        let location = [get_udta_location(&moov), get_meta_value(&moov, ISO6709_KEY)]
+            .into_iter()
+            .flatten()
+            .find(|location| parse_iso6709(location).is_some());
+        let creation_date = get_meta_value(&moov, CREATION_DATE_KEY);
+        let track = extract_track_properties(&moov);
+        let properties = PhotoProperties {
+            make: get_meta_value(&moov, MAKE_KEY).map(str::to_string),
+            model: get_meta_value(&moov, MODEL_KEY).map(str::to_string),
+            width: track.map(|(width, _, _)| width),
+            height: track.map(|(_, height, _)| height),
+            orientation: track.map(|(_, _, orientation)| orientation),
+            altitude: location.and_then(parse_iso6709_altitude),
+            ..Default::default()
+        };
+
+        Ok(Some(PhotoMetadata {
+            coord: location.and_then(parse_iso6709),
+            date_time: creation_date.and_then(MyDateTime::from_iso8601),
+            utc_offset_seconds: creation_date.and_then(iso8601_offset_seconds),
+            utc_date_time: extract_creation_time(&moov),
+            properties,
+            ..Default::default()
+        }))
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::find_root::find_root;
+use crate::types::WalkOptions;
+
+/// Already anchored at the folder, unlike user patterns.
+const BUILTIN_EXCLUDES: &[&str] = &["categorized", "**/@eaDir", "**/.thumbnails", "**/.*"];
+
+/// Decides which entries below the roots a scan reads, following `WalkOptions`. Patterns
+/// are relative to the root an entry lies in.
+pub struct WalkFilter {
+    roots: Vec<PathBuf>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    /// Folders the application writes to itself, never read however the patterns go.
+    excluded_dirs: Vec<PathBuf>,
+}
+
+fn anchor(pattern: &str) -> String {
+    if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    }
+}
+
+fn build_glob_set<I>(patterns: I) -> Result<GlobSet, globset::Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(GlobBuilder::new(&pattern).literal_separator(true).build()?);
+    }
+    builder.build()
+}
+
+impl WalkFilter {
+    pub fn new(roots: &[PathBuf], options: &WalkOptions) -> Result<WalkFilter, globset::Error> {
+        let include = if options.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(options.include.iter().map(|p| anchor(p)))?)
+        };
+
+        let builtin = BUILTIN_EXCLUDES
+            .iter()
+            .filter(|_| options.builtin_excludes)
+            .map(|p| p.to_string());
+        let exclude = build_glob_set(options.exclude.iter().map(|p| anchor(p)).chain(builtin))?;
+
+        Ok(WalkFilter {
+            roots: roots.to_vec(),
+            include,
+            exclude,
+            max_depth: options.max_depth,
+            follow_symlinks: options.follow_symlinks,
+            excluded_dirs: Vec::new(),
+        })
+    }
+
+    pub fn excluding(mut self, dir: &Path) -> WalkFilter {
+        self.excluded_dirs.push(dir.to_path_buf());
+        self
+    }
+
+    /// Whether a walk should enter `path`, a directory, or read it, a file. Directories
+    /// above `path` are assumed to have been accepted already.
+    pub fn accepts(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(relative) = find_root(&self.roots, path).and_then(|r| path.strip_prefix(r).ok())
+        else {
+            return false;
+        };
+        if self.excluded_dirs.iter().any(|dir| path.starts_with(dir)) {
+            return false;
+        }
+        if relative.as_os_str().is_empty() {
+            return true;
+        }
+        if self
+            .max_depth
+            .is_some_and(|max_depth| relative.components().count() > max_depth)
+        {
+            return false;
+        }
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+
+        is_dir || self.include.as_ref().is_none_or(|i| i.is_match(relative))
+    }
+
+    /// Like `accepts`, but also checks the directories between the root and `path`.
+    pub fn accepts_nested(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(root) = find_root(&self.roots, path) else {
+            return false;
+        };
+
+        path.ancestors()
+            .skip(1)
+            .take_while(|ancestor| *ancestor != root)
+            .all(|ancestor| self.accepts(ancestor, true))
+            && self.accepts(path, is_dir)
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::find_duplicates::mark_duplicates;
+use crate::gpx_track::GpxTrack;
+use crate::launch_pipeline_for_directory::cluster_photos;
+use crate::metadata_cache::{MetadataCache, CACHE_FILENAME};
+use crate::metadata_source::MetadataSources;
+use crate::scan_directory::*;
+use crate::scan_progress::ScanProgress;
+use crate::session::Session;
+use crate::types::*;
+use crate::walk_filter::WalkFilter;
+
+/// How long the folder has to stay quiet before it is rescanned. Imports arrive as bursts
+/// of events.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Scanned folders together with everything needed to rescan parts of them.
+pub struct WatchedDirectory {
+    pub roots: Vec<PathBuf>,
+    pub sources: MetadataSources,
+    pub cache: Arc<MetadataCache>,
+    pub filter: WalkFilter,
+    pub track: Option<GpxTrack>,
+    pub concurrency: usize,
+    pub similarity: Option<SimilarityOptions>,
+    pub clustering: ClusteringParameters,
+    pub photos: BTreeMap<PathBuf, Photo>,
+    pub report: ScanReport,
+    /// The scan generation the watch belongs to.
+    pub generation: u64,
+}
+
+impl WatchedDirectory {
+    /// Removes what is known about the files at or below `path`.
+    fn forget(&mut self, path: &Path) -> bool {
+        let known: Vec<PathBuf> = self
+            .photos
+            .keys()
+            .cloned()
+            .chain(
+                self.report
+                    .skipped
+                    .iter()
+                    .map(|skipped| PathBuf::from(&skipped.filepath.0))
+                    .chain(self.report.sidecar_files().map(PathBuf::from)),
+            )
+            .filter(|known| known.starts_with(path))
+            .collect();
+
+        for known in &known {
+            let was_file = self.photos.remove(known).is_some()
+                | self
+                    .report
+                    .forget(&Filepath(known.to_string_lossy().to_string()));
+            if was_file && self.report.files_seen > 0 {
+                self.report.files_seen -= 1;
+            }
+        }
+
+        !known.is_empty()
+    }
+
+    /// Rescans the files at or below `paths`. Returns whether anything changed.
+    fn update(&mut self, paths: BTreeSet<PathBuf>, progress: &ScanProgress) -> bool {
+        let mut changed = false;
+        for path in &paths {
+            changed |= self.forget(path);
+        }
+
+        progress.start();
+        let ctx = ScanContext {
+            roots: &self.roots,
+            sources: &self.sources,
+            cache: &self.cache,
+            filter: &self.filter,
+            track: self.track.as_ref(),
+            concurrency: self.concurrency,
+            progress,
+            perceptual_hashes: self.similarity.is_some(),
+        };
+
+        let mut files = BTreeSet::new();
+        for path in &paths {
+            let is_dir = path.is_dir();
+            if !self.filter.accepts_nested(path, is_dir) {
+                continue;
+            }
+            if is_dir {
+                files.extend(walk_directory(path, &ctx, &mut self.report));
+            } else if path.is_file() {
+                progress.file_seen();
+                files.insert(path.clone());
+            }
+        }
+        let files: Vec<PathBuf> = files.into_iter().collect();
+        let results = scan_files(&files, &ctx);
+        progress.finish();
+
+        let sidecars = self.cache.sidecar_paths();
+        for (file, result) in files.iter().zip(results) {
+            self.report.files_seen += 1;
+            match result {
+                Ok(photo) => {
+                    self.photos.insert(file.clone(), photo);
+                }
+                Err(_) if sidecars.contains(file) => {
+                    self.report
+                        .add_sidecar(Filepath(file.to_string_lossy().to_string()));
+                }
+                Err(reason) => {
+                    let filepath = Filepath(file.to_string_lossy().to_string());
+                    self.report.skip(filepath, reason);
+                }
+            }
+        }
+        self.report.files_with_location = self.photos.len();
+
+        if let Err(e) = self.cache.save() {
+            eprintln!("Cannot save metadata cache: {}", e);
+        }
+
+        changed || !files.is_empty()
+    }
+
+    /// Drops the recomputed tree if a new scan has replaced the watch in the meantime.
+    fn publish(&self, session: &Session) {
+        let mut photos: Vec<Photo> = self.photos.values().cloned().collect();
+        mark_duplicates(&mut photos);
+        let tree = cluster_photos(photos, self.similarity.as_ref(), &self.clustering);
+        let published = session.publish(
+            self.generation,
+            tree,
+            self.report.clone(),
+            self.clustering.clone(),
+        );
+        if published {
+            session.tree_events.tree_changed();
+        }
+    }
+}
+
+/// Keeps the watch started by `watch_directory` running; dropping it stops the watch.
+pub struct DirectoryWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+/// Rescans the files of `directory` as they change and publishes the recomputed tree to
+/// `session`.
+pub fn watch_directory(
+    directory: WatchedDirectory,
+    session: Arc<Session>,
+) -> notify::Result<DirectoryWatcher> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                let _ = sender.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Watch error: {}", e),
+        })?;
+    for root in &directory.roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    let mut directory = directory;
+    thread::spawn(move || {
+        // Ends once the watcher, and with it the sender, is dropped.
+        while let Ok(paths) = receiver.recv() {
+            let mut paths: BTreeSet<PathBuf> = paths.into_iter().collect();
+            while let Ok(more) = receiver.recv_timeout(SETTLE_TIME) {
+                paths.extend(more);
+            }
+            // Saving the cache is itself a change in the folder.
+            paths.retain(|path| !path.ends_with(CACHE_FILENAME));
+
+            if !paths.is_empty() && directory.update(paths, &session.scan_progress) {
+                directory.publish(&session);
+            }
+        }
+    });
+
+    Ok(DirectoryWatcher { _watcher: watcher })
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+
+use crate::capture_time::iso8601_offset_seconds;
+use crate::metadata_source::*;
+use crate::types::*;
+
+/// Whether `IMG_1234.xmp` belongs to `path` rather than to another file named
+/// `IMG_1234`. Next to the JPEG shot with it, it holds the develop settings of the raw.
+fn owns_stem_sidecar(path: &Path) -> bool {
+    let others = media_files_sharing_stem(path);
+    if has_extension(path, &RAW_EXTENSIONS) {
+        !others
+            .iter()
+            .any(|other| has_extension(other, &RAW_EXTENSIONS))
+    } else {
+        others.is_empty()
+    }
+}
+
+/// Sidecar names used by the common raw developers: `IMG_1234.xmp` (Lightroom,
+/// Capture One) and `IMG_1234.CR2.xmp` (darktable, digiKam).
+fn find_sidecar(path: &Path) -> Option<PathBuf> {
+    let mut with_appended = path.as_os_str().to_owned();
+    with_appended.push(".xmp");
+    let with_appended = PathBuf::from(with_appended);
+    if with_appended.is_file() {
+        return Some(with_appended);
+    }
+
+    [path.with_extension("xmp"), path.with_extension("XMP")]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .filter(|_| owns_stem_sidecar(path))
+}
+
+/// Returns the value of an XMP property written either as an attribute
+/// (`exif:GPSLatitude="..."`) or as an element (`<exif:GPSLatitude>...</exif:GPSLatitude>`).
+fn get_property<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    let as_attribute = format!("{}=\"", name);
+    if let Some(start) = xmp.find(&as_attribute) {
+        let value = &xmp[start + as_attribute.len()..];
+        return value.split('"').next();
+    }
+
+    let as_element = format!("<{}>", name);
+    let start = xmp.find(&as_element)? + as_element.len();
+    let value = &xmp[start..];
+    value.split('<').next().map(str::trim)
+}
+
+/// XMP stores coordinates as `DDD,MM,SSk` or `DDD,MM.mmk` where `k` is one of `NSEW`.
+fn parse_coordinate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let direction = value.chars().last()?.to_ascii_uppercase();
+    let sign = match direction {
+        'N' | 'E' => 1.0,
+        'S' | 'W' => -1.0,
+        _ => return value.parse().ok(),
+    };
+
+    let parts: Vec<f64> = value[..value.len() - 1]
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    let decimal = match parts.as_slice() {
+        [deg] => *deg,
+        [deg, min] => deg + min / 60.,
+        [deg, min, sec] => deg + min / 60. + sec / 3600.,
+        _ => return None,
+    };
+
+    Some(sign * decimal)
+}
+
+/// XMP writes rationals as `num/denom`.
+fn parse_rational(value: &str) -> Option<f64> {
+    match value.trim().split_once('/') {
+        Some((num, denom)) => {
+            let denom: f64 = denom.trim().parse().ok()?;
+            (denom != 0.).then_some(num.trim().parse::<f64>().ok()? / denom)
+        }
+        None => value.trim().parse().ok(),
+    }
+}
+
+fn extract_properties(xmp: &str) -> PhotoProperties {
+    let text = |name| {
+        get_property(xmp, name)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let number = |name| get_property(xmp, name)?.trim().parse().ok();
+
+    let altitude = get_property(xmp, "exif:GPSAltitude").and_then(parse_rational);
+    let below_sea_level = get_property(xmp, "exif:GPSAltitudeRef") == Some("1");
+
+    PhotoProperties {
+        make: text("tiff:Make"),
+        model: text("tiff:Model"),
+        lens: text("exifEX:LensModel").or_else(|| text("aux:Lens")),
+        width: number("exif:PixelXDimension").or_else(|| number("tiff:ImageWidth")),
+        height: number("exif:PixelYDimension").or_else(|| number("tiff:ImageLength")),
+        orientation: get_property(xmp, "tiff:Orientation").and_then(|o| o.trim().parse().ok()),
+        altitude: altitude.map(|a| if below_sea_level { -a } else { a }),
+    }
+}
+
+/// Reads GPS position and capture time from an `.xmp` sidecar next to the image or video.
+pub struct XmpMetadataSource {}
+
+impl MetadataSource for XmpMetadataSource {
+    fn extract(&self, path: &Path) -> Result<Option<PhotoMetadata>, SkipReason> {
+        if !is_media_file(path) {
+            return Ok(None);
+        }
+
+        let Some(sidecar) = find_sidecar(path) else {
+            return Ok(None);
+        };
+        let xmp = std::fs::read_to_string(sidecar)?;
+
+        let lat = get_property(&xmp, "exif:GPSLatitude").and_then(parse_coordinate);
+        let lon = get_property(&xmp, "exif:GPSLongitude").and_then(parse_coordinate);
+        let coord = match (lat, lon) {
+            (Some(lat), Some(lon)) => Some(Coord { lat, lon }),
+            _ => None,
+        };
+
+        let date_time_original = get_property(&xmp, "exif:DateTimeOriginal");
+        let date_time = date_time_original.and_then(MyDateTime::from_iso8601);
+        let utc_offset_seconds = date_time_original.and_then(iso8601_offset_seconds);
+
+        Ok(Some(PhotoMetadata {
+            coord,
+            date_time,
+            utc_offset_seconds,
+            properties: extract_properties(&xmp),
+            ..Default::default()
+        }))
+    }
+
+    fn sidecars(&self, path: &Path) -> Vec<PathBuf> {
+        if !is_media_file(path) {
+            return Vec::new();
+        }
+
+        find_sidecar(path).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn gives_the_shared_sidecar_to_the_raw() {
+        let dir = TestDir::new();
+        let raw = dir.write("IMG_1234.CR2", "");
+        let jpeg = dir.write("IMG_1234.JPG", "");
+        let sidecar = dir.write("IMG_1234.xmp", "");
+
+        assert_eq!(find_sidecar(&raw), Some(sidecar));
+        assert_eq!(find_sidecar(&jpeg), None);
+    }
+
+    #[test]
+    fn gives_the_shared_sidecar_to_nobody_without_a_raw() {
+        let dir = TestDir::new();
+        let jpeg = dir.write("IMG_1234.jpg", "");
+        let video = dir.write("IMG_1234.mov", "");
+        dir.write("IMG_1234.xmp", "");
+
+        assert_eq!(find_sidecar(&jpeg), None);
+        assert_eq!(find_sidecar(&video), None);
+    }
+
+    #[test]
+    fn prefers_the_sidecar_named_after_the_whole_file() {
+        let dir = TestDir::new();
+        let raw = dir.write("IMG_1234.CR2", "");
+        let jpeg = dir.write("IMG_1234.JPG", "");
+        let jpeg_sidecar = dir.write("IMG_1234.JPG.xmp", "");
+        let shared = dir.write("IMG_1234.xmp", "");
+
+        assert_eq!(find_sidecar(&jpeg), Some(jpeg_sidecar));
+        assert_eq!(find_sidecar(&raw), Some(shared));
+    }
+
+    #[test]
+    fn takes_the_stem_sidecar_of_a_lone_file() {
+        let dir = TestDir::new();
+        let jpeg = dir.write("IMG_1234.jpg", "");
+        let sidecar = dir.write("IMG_1234.xmp", "");
+
+        assert_eq!(find_sidecar(&jpeg), Some(sidecar));
+    }
+}